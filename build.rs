@@ -0,0 +1,10 @@
+use std::error::Error;
+
+// Generates the gRPC service from the protobuf definition, with a vendored
+// protoc so the build does not depend on one being installed
+fn main() -> Result<(), Box<dyn Error>> {
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    tonic_build::compile_protos("proto/processor.proto")?;
+
+    Ok(())
+}
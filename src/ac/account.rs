@@ -1,183 +1,570 @@
+use chrono::{DateTime, Utc};
+
 use crate::storage::{Storage, StoreKey};
+use crate::tx::currency::Currency;
+use crate::tx::id::{ClientId, TxId};
+use crate::tx::journal;
+use crate::tx::rates;
 use crate::tx::transaction::Dispute;
 use crate::tx::transaction::{self, Transaction};
 use once_cell::sync::Lazy;
 use serde::Serialize;
+use std::collections::HashMap;
 use std::sync::Mutex;
 
+use super::authorization;
+use super::events;
 use super::export;
-
-pub static ACCOUNTS: Lazy<Mutex<Storage<u16, Account>>> = Lazy::new(|| Mutex::new(Storage::new()));
-pub static CLIENTS: Lazy<Mutex<Vec<u16>>> = Lazy::new(|| Mutex::new(vec![]));
-
+use super::fee;
+use super::history;
+use super::ledger::{self, Book, Posting};
+use super::limits;
+use super::outcome::{Outcome, Reject};
+use super::review::{self, Status};
+use super::rules::{self, Action};
+
+/// One balance bucket per client and currency.
+pub type AccountKey = (ClientId, Currency);
+
+/// Every balance bucket, keyed by client and currency.
+pub static ACCOUNTS: Lazy<Mutex<Storage<AccountKey, Account>>> =
+    Lazy::new(|| Mutex::new(Storage::new()));
+/// Buckets in the order they were opened, for exports that keep input order.
+pub static CLIENTS: Lazy<Mutex<Vec<AccountKey>>> = Lazy::new(|| Mutex::new(vec![]));
+// currencies of each client in the order they were opened, so the buckets of
+// a client are found without going through every key
+static CURRENCIES: Lazy<Mutex<HashMap<ClientId, Vec<Currency>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+static PROCESSING: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// The balances of one client in one currency.
 #[derive(Debug, Copy, Clone, PartialEq, Serialize)]
 pub struct Account {
-    pub client: u16,
+    /// The client the balances belong to.
+    pub client: ClientId,
+    /// The currency of the balances.
+    pub currency: Currency,
+    /// Funds the client can withdraw, transfer or convert.
     pub available: f32,
+    /// Funds under dispute or authorized but not yet captured.
     pub held: f32,
+    /// `available + held`.
     pub total: f32,
+    /// Set by a chargeback or a freeze; a locked client moves no funds until unlocked.
     pub locked: bool,
+    /// Set when the client is closed; a closed client takes no transactions.
+    pub closed: bool,
+    /// Timestamp of the last row that moved funds in this bucket.
+    pub updated: Option<DateTime<Utc>>,
 }
 
 impl Account {
-    pub fn new(client: u16, available: f32, held: f32) -> Self {
+    /// An unlocked, open bucket in the default currency.
+    pub fn new(client: ClientId, available: f32, held: f32) -> Self {
         let total = available + held;
         let locked = false;
+        let closed = false;
 
         Self {
             client,
+            currency: Currency::default(),
             available,
             held,
             total,
             locked,
+            closed,
+            updated: None,
         }
     }
 
+    /// Credits the amount of `tranx` to available funds.
     pub fn deposit(&mut self, tranx: &Transaction) {
-        let available = self.available + tranx.amount;
-        let total = self.total + tranx.amount;
-        self.available = round_up(available);
-        self.total = round_up(total);
+        self.post(tranx, Book::External, Book::Available(self.client));
     }
 
+    /// Debits available funds, leaving the account as it was when they do not cover `tranx`.
     pub fn withdraw(&mut self, tranx: &Transaction) {
         if tranx.amount <= self.available {
-            let available = self.available - tranx.amount;
-            let total = self.total - tranx.amount;
-            self.available = round_up(available);
-            self.total = round_up(total);
+            self.post(tranx, Book::Available(self.client), Book::External);
         }
     }
 
+    /// Moves the disputed amount from available to held.
     pub fn dispute(&mut self, tranx: &Transaction) {
         if tranx.amount <= self.available {
-            let available = self.available - tranx.amount;
-            let held = self.held + tranx.amount;
-
-            self.available = round_up(available);
-            self.held = round_up(held);
+            self.post(tranx, Book::Available(self.client), Book::Held(self.client));
         }
     }
 
+    /// Gives a resolved amount back from held to available.
     pub fn resolve(&mut self, tranx: &Transaction) {
         if tranx.amount <= self.held {
-            let available = self.available + tranx.amount;
-            let held = self.held - tranx.amount;
+            self.post(tranx, Book::Held(self.client), Book::Available(self.client));
+        }
+    }
 
-            self.available = round_up(available);
-            self.held = round_up(held);
+    /// Takes held funds back and locks the bucket. A charged back transfer is
+    /// credited back to the sender rather than the card network.
+    pub fn chargeback(&mut self, tranx: &Transaction) -> Option<Posting> {
+        if tranx.amount > self.held {
+            return None;
         }
+
+        let credit = if tranx.r#type == "transfer" {
+            Book::Available(tranx.client)
+        } else {
+            Book::Chargebacks
+        };
+
+        let posting = self.post(tranx, Book::Held(self.client), credit);
+        self.locked = true;
+
+        Some(posting)
     }
 
-    pub fn chargeback(&mut self, tranx: &Transaction) {
+    /// Moves an authorized amount from available to held.
+    pub fn authorize(&mut self, tranx: &Transaction) {
+        if tranx.amount <= self.available {
+            self.post(tranx, Book::Available(self.client), Book::Held(self.client));
+        }
+    }
+
+    /// Pays out a captured amount from held funds.
+    pub fn capture(&mut self, tranx: &Transaction) {
+        if tranx.amount <= self.held {
+            self.post(tranx, Book::Held(self.client), Book::External);
+        }
+    }
+
+    /// Gives held funds of a void or expired authorization back to the client.
+    pub fn release(&mut self, tranx: &Transaction) {
         if tranx.amount <= self.held {
-            let held = self.held - tranx.amount;
-            let total = self.total - tranx.amount;
+            self.post(tranx, Book::Held(self.client), Book::Available(self.client));
+        }
+    }
 
-            self.held = round_up(held);
-            self.total = round_up(total);
-            self.locked = true;
+    /// Debits a fee from available funds into the fees book.
+    pub fn charge_fee(&mut self, tranx: &Transaction) {
+        if tranx.amount <= self.available {
+            self.post(tranx, Book::Available(self.client), Book::Fees);
+        }
+    }
+
+    fn post(&mut self, tranx: &Transaction, debit: Book, credit: Book) -> Posting {
+        let posting = ledger::post(tranx.tx, self.currency, debit, credit, tranx.amount);
+        self.apply(&posting);
+
+        posting
+    }
+
+    /// Balances only ever change by applying the postings that touch this account's books.
+    pub fn apply(&mut self, posting: &Posting) {
+        if posting.currency != self.currency {
+            return;
+        }
+
+        let mut net = 0.0;
+        for (book, amount) in [
+            (posting.debit, -posting.amount),
+            (posting.credit, posting.amount),
+        ] {
+            match book {
+                Book::Available(client) if client == self.client => {
+                    self.available = round_up(self.available + amount);
+                    net += amount;
+                }
+                Book::Held(client) if client == self.client => {
+                    self.held = round_up(self.held + amount);
+                    net += amount;
+                }
+                _ => {}
+            }
+        }
+
+        // moves between a client's own books leave the total untouched
+        if net != 0.0 {
+            self.total = round_up(self.total + net);
         }
     }
+
+    /// Lifts a lock set by a chargeback or a freeze.
+    pub fn unlock(&mut self) {
+        self.locked = false;
+    }
+
+    /// Locks the bucket until it is unlocked.
+    pub fn freeze(&mut self) {
+        self.locked = true;
+    }
+
+    /// Marks the bucket closed for good.
+    pub fn close(&mut self) {
+        self.closed = true;
+    }
 }
 
 impl StoreKey for Account {
-    type Key = u16;
+    type Key = AccountKey;
     fn key(&self) -> Self::Key {
-        self.client
+        (self.client, self.currency)
     }
 }
 
-fn round_up(value: f32) -> f32 {
+/// Cuts a balance down to the four decimal places the engine keeps.
+pub fn round_up(value: f32) -> f32 {
     (value * 10000.0).floor() / 10000.0
 }
 
+/// Writes every account to stdout as CSV.
 pub fn export() {
     export::run();
 }
 
-pub fn process_deposit(tranx: &Transaction) {
-    if tranx.r#type != "deposit" {
-        return;
+/// Applies one transaction to the accounts and journals the outcome.
+///
+/// A transaction the engine will not apply comes back as
+/// [`Outcome::Rejected`] and leaves every balance as it was.
+pub fn process(tranx: &Transaction) -> Outcome {
+    let posted = ledger::posted();
+
+    // holds that ran out before this row are released first
+    if let Some(now) = authorization::clock_time(tranx) {
+        authorization::expire(now);
+    }
+
+    // an approval publishes the changes of the row it releases, see `process_approve`
+    let before = match tranx.r#type.as_str() {
+        "approve" => None,
+        _ => events::snapshot(tranx),
+    };
+
+    // risk rules run before the transaction touches any balance, except on
+    // review decisions, which release what the rules held
+    let hit = match tranx.r#type.as_str() {
+        "approve" | "decline" => None,
+        _ => rules::check(tranx),
+    };
+    let outcome = match hit.as_ref().map(|rule| (rule.action, rule.name.as_str())) {
+        None | Some((Action::Allow, _)) => dispatch(tranx),
+        Some((Action::Reject, _)) => Outcome::Rejected(Reject::RuleRejected),
+        // a replay of a stored row is rejected as usual instead of parked over its record
+        Some((Action::Hold, rule)) => match check_duplicate(tranx).filter(|_| stores_tx(tranx)) {
+            Some(reject) => Outcome::Rejected(reject),
+            None => review::park(tranx, rule),
+        },
+        Some((Action::Lock, _)) => {
+            get_account(tranx.client, tranx.currency);
+            freeze_client(tranx.client);
+            Outcome::Rejected(Reject::AccountLocked)
+        }
+    };
+
+    journal::record_rule(tranx, outcome, hit.as_ref().map(|rule| rule.name.as_str()));
+    history::record(tranx, outcome);
+
+    if outcome.is_applied() {
+        fee::charge(tranx);
     }
 
-    let tx_exists: bool;
-    tx_exists = transaction::TRANSACTIONS.lock().unwrap().exists(tranx.tx);
+    if let Some(at) = tranx.timestamp {
+        stamp(posted, at);
+    }
+
+    events::publish(tranx, before);
+
+    outcome
+}
+
+// Marks the buckets moved by the postings of a row with the row's timestamp
+fn stamp(posted: usize, at: DateTime<Utc>) {
+    let accounts = ACCOUNTS.lock().unwrap();
+
+    for posting in ledger::since(posted) {
+        for book in [posting.debit, posting.credit] {
+            if let Book::Available(client) | Book::Held(client) = book {
+                accounts.modify((client, posting.currency), |acct| {
+                    if let Some(acct) = acct {
+                        acct.updated = Some(at);
+                    }
+                });
+            }
+        }
+    }
+}
+
+fn dispatch(tranx: &Transaction) -> Outcome {
+    match tranx.r#type.as_str() {
+        "deposit" => process_deposit(tranx),
+        "withdrawal" => process_withdrawal(tranx),
+        "dispute" => process_dispute(tranx),
+        "resolve" => process_resolve(tranx),
+        "chargeback" => process_chargeback(tranx),
+        "unlock" => process_unlock(tranx),
+        "freeze" => process_freeze(tranx),
+        "close" => process_close(tranx),
+        "transfer" => process_transfer(tranx),
+        "convert" => process_convert(tranx),
+        "authorize" => process_authorize(tranx),
+        "capture" => process_capture(tranx),
+        "void" => process_void(tranx),
+        "approve" => process_approve(tranx),
+        "decline" => process_decline(tranx),
+        _ => Outcome::Rejected(Reject::UnsupportedType),
+    }
+}
+
+/// Processes a batch in order without rows of another caller in between, for
+/// services that submit from several tasks at once.
+///
+/// This blocks on the engine lock, so async callers should run it on a
+/// blocking thread.
+pub fn process_all(batch: &[Transaction]) -> Vec<Outcome> {
+    let _guard = PROCESSING.lock().unwrap();
+
+    batch.iter().map(process).collect()
+}
+
+/// Credits a deposit to the client, unless the account is locked or closed.
+pub fn process_deposit(tranx: &Transaction) -> Outcome {
+    if tranx.r#type != "deposit" {
+        return Outcome::Rejected(Reject::UnsupportedType);
+    }
 
     // handle duplicates
-    if tx_exists {
-        return;
+    if let Some(reject) = check_duplicate(tranx) {
+        return Outcome::Rejected(reject);
     }
 
-    let acct = get_account(tranx.client);
+    let acct = get_account(tranx.client, tranx.currency);
     // ignore if account is frozen
     if acct.locked {
-        return;
+        return Outcome::Rejected(Reject::AccountLocked);
+    }
+
+    // closed accounts no longer take new funds
+    if acct.closed {
+        return Outcome::Rejected(Reject::AccountClosed);
     }
 
-    let u_account: Option<Account> = ACCOUNTS.lock().unwrap().modify(tranx.client, |acct| {
-        let a = if let Some(acc) = acct {
+    let u_account: Option<Account> = ACCOUNTS.lock().unwrap().modify(acct.key(), |acct| {
+        if let Some(acc) = acct {
             acc.deposit(tranx);
             Some(*acc)
         } else {
             None
-        };
-        return a;
+        }
     });
 
     if let Some(acct) = u_account {
         ACCOUNTS.lock().unwrap().insert(acct);
         save_transaction(tranx);
     }
+
+    Outcome::Applied
 }
 
-pub fn process_withdrawal(tranx: &Transaction) {
+/// Debits a withdrawal within the withdrawal limits and the available funds.
+pub fn process_withdrawal(tranx: &Transaction) -> Outcome {
     if tranx.r#type != "withdrawal" {
-        return;
+        return Outcome::Rejected(Reject::UnsupportedType);
     }
 
-    let tx_exists: bool;
-
-    tx_exists = transaction::TRANSACTIONS.lock().unwrap().exists(tranx.tx);
-
     // handle duplicates
-    if tx_exists {
-        return;
+    if let Some(reject) = check_duplicate(tranx) {
+        return Outcome::Rejected(reject);
     }
 
-    let acct = get_account(tranx.client);
+    let acct = get_account(tranx.client, tranx.currency);
 
     // ignore if account is frozen
     if acct.locked {
-        return;
+        return Outcome::Rejected(Reject::AccountLocked);
     }
 
-    let u_account: Option<Account> = ACCOUNTS.lock().unwrap().modify(tranx.client, |acct| {
-        let a = if let Some(acc) = acct {
+    if acct.closed {
+        return Outcome::Rejected(Reject::AccountClosed);
+    }
+
+    // compliance limits apply before funds are looked at
+    if let Some(reject) = limits::check(tranx) {
+        return Outcome::Rejected(reject);
+    }
+
+    // a rejected withdrawal is not stored, so it can never be disputed
+    if tranx.amount > acct.available {
+        return Outcome::Rejected(Reject::InsufficientFunds);
+    }
+
+    let u_account: Option<Account> = ACCOUNTS.lock().unwrap().modify(acct.key(), |acct| {
+        if let Some(acc) = acct {
             acc.withdraw(tranx);
             Some(*acc)
         } else {
             None
-        };
-        return a;
+        }
     });
 
     if let Some(acct) = u_account {
         ACCOUNTS.lock().unwrap().insert(acct);
         save_transaction(tranx);
     }
+
+    Outcome::Applied
 }
 
-pub fn process_dispute(tranx: &Transaction) {
+/// Moves funds between two clients in one step.
+pub fn process_transfer(tranx: &Transaction) -> Outcome {
+    if tranx.r#type != "transfer" {
+        return Outcome::Rejected(Reject::UnsupportedType);
+    }
+
+    let to_client = match tranx.to_client {
+        Some(to_client) if to_client != tranx.client => to_client,
+        _ => return Outcome::Rejected(Reject::InvalidCounterparty),
+    };
+
+    // a negative transfer would pull funds out of the receiving account
+    if tranx.amount <= 0.0 {
+        return Outcome::Rejected(Reject::InvalidAmount);
+    }
+
+    // handle duplicates
+    if let Some(reject) = check_duplicate(tranx) {
+        return Outcome::Rejected(reject);
+    }
+
+    let source = get_account(tranx.client, tranx.currency);
+
+    // ignore if account is frozen
+    if source.locked {
+        return Outcome::Rejected(Reject::AccountLocked);
+    }
+
+    if source.closed {
+        return Outcome::Rejected(Reject::AccountClosed);
+    }
+
+    if tranx.amount > source.available {
+        return Outcome::Rejected(Reject::InsufficientFunds);
+    }
+
+    let destination = get_account(to_client, tranx.currency);
+
+    if destination.locked {
+        return Outcome::Rejected(Reject::AccountLocked);
+    }
+
+    if destination.closed {
+        return Outcome::Rejected(Reject::AccountClosed);
+    }
+
+    // hold the store for both legs so the transfer is applied in one step
+    let accounts = ACCOUNTS.lock().unwrap();
+    let posting = ledger::post(
+        tranx.tx,
+        tranx.currency,
+        Book::Available(tranx.client),
+        Book::Available(to_client),
+        tranx.amount,
+    );
+    accounts.modify(source.key(), |acct| acct.unwrap().apply(&posting));
+    accounts.modify(destination.key(), |acct| acct.unwrap().apply(&posting));
+    drop(accounts);
+
+    save_transaction(tranx);
+
+    Outcome::Applied
+}
+
+/// Converts funds between two currency buckets of a client at the loaded rate.
+pub fn process_convert(tranx: &Transaction) -> Outcome {
+    if tranx.r#type != "convert" {
+        return Outcome::Rejected(Reject::UnsupportedType);
+    }
+
+    let to_currency = match tranx.to_currency {
+        Some(to_currency) if to_currency != tranx.currency => to_currency,
+        _ => return Outcome::Rejected(Reject::InvalidCurrency),
+    };
+
+    // a negative conversion would debit the target currency instead
+    if tranx.amount <= 0.0 {
+        return Outcome::Rejected(Reject::InvalidAmount);
+    }
+
+    let quote = match rates::quote(tranx) {
+        Some(quote) => quote,
+        None => return Outcome::Rejected(Reject::UnknownRate),
+    };
+
+    if rates::creates_value(tranx, &quote) {
+        return Outcome::Rejected(Reject::ValueCreated);
+    }
+
+    // handle duplicates
+    if let Some(reject) = check_duplicate(tranx) {
+        return Outcome::Rejected(reject);
+    }
+
+    let source = get_account(tranx.client, tranx.currency);
+
+    // ignore if account is frozen
+    if source.locked {
+        return Outcome::Rejected(Reject::AccountLocked);
+    }
+
+    if source.closed {
+        return Outcome::Rejected(Reject::AccountClosed);
+    }
+
+    if tranx.amount > source.available {
+        return Outcome::Rejected(Reject::InsufficientFunds);
+    }
+
+    let destination = get_account(tranx.client, to_currency);
+
+    // hold the store for both buckets so the conversion is applied in one step
+    let accounts = ACCOUNTS.lock().unwrap();
+    let debit = ledger::post(
+        tranx.tx,
+        tranx.currency,
+        Book::Available(tranx.client),
+        Book::Exchange,
+        tranx.amount,
+    );
+    let credit = ledger::post(
+        tranx.tx,
+        to_currency,
+        Book::Exchange,
+        Book::Available(tranx.client),
+        quote.amount,
+    );
+    accounts.modify(source.key(), |acct| acct.unwrap().apply(&debit));
+    accounts.modify(destination.key(), |acct| acct.unwrap().apply(&credit));
+    drop(accounts);
+
+    save_transaction(tranx);
+
+    Outcome::Applied
+}
+
+/// Holds part or all of an applied transaction until it is resolved or charged back.
+pub fn process_dispute(tranx: &Transaction) -> Outcome {
     if tranx.r#type != "dispute" {
-        return;
+        return Outcome::Rejected(Reject::UnsupportedType);
+    }
+
+    // only an empty or zero amount means the whole transaction
+    if tranx.amount < 0.0 {
+        return Outcome::Rejected(Reject::InvalidAmount);
     }
 
     let tx_exists: bool = transaction::TRANSACTIONS.lock().unwrap().exists(tranx.tx);
-    let dispute_exists: bool = transaction::DISPUTES.lock().unwrap().exists(tranx.tx);
 
-    if !tx_exists || dispute_exists {
-        return;
+    // only applied rows are stored, so money that never moved is unknown here
+    if !tx_exists {
+        return Outcome::Rejected(Reject::UnknownTx);
     }
 
     let stored_tranx: Transaction = transaction::TRANSACTIONS
@@ -185,45 +572,93 @@ pub fn process_dispute(tranx: &Transaction) {
         .unwrap()
         .read(tranx.tx, |trx| trx.unwrap().clone());
 
-    let acct = get_account(tranx.client);
+    // conversions move funds between a client's own balances, and
+    // authorizations are settled by capture or void instead
+    if stored_tranx.r#type == "convert" || stored_tranx.r#type == "authorize" {
+        return Outcome::Rejected(Reject::NotDisputable);
+    }
+
+    let stored_dispute: Option<Dispute> = transaction::DISPUTES
+        .lock()
+        .unwrap()
+        .read(tranx.tx, |dis| dis.copied());
+
+    // earlier partial disputes reduce what is left to dispute
+    let disputed = stored_dispute.map_or(0.0, |dis| dis.amount);
+    let remaining = round_up(stored_tranx.amount - disputed);
+
+    if remaining <= 0.0 {
+        return Outcome::Rejected(Reject::DisputeExists);
+    }
+
+    // a dispute without an amount covers whatever is left of the transaction
+    let amount = if tranx.amount == 0.0 {
+        remaining
+    } else {
+        tranx.amount
+    };
+
+    if amount > remaining {
+        return Outcome::Rejected(Reject::ExceedsUndisputed);
+    }
+
+    let client = disputed_client(tranx, &stored_tranx);
+    // disputes apply in the currency of the original transaction
+    let acct = get_account(client, stored_tranx.currency);
 
     // ignore if account is frozen
     if acct.locked {
-        return;
+        return Outcome::Rejected(Reject::AccountLocked);
     }
 
-    let u_account: Option<Account> = ACCOUNTS.lock().unwrap().modify(tranx.client, |acct| {
-        let a = if let Some(acc) = acct {
-            acc.dispute(&stored_tranx);
+    if amount > acct.available {
+        return Outcome::Rejected(Reject::InsufficientFunds);
+    }
+
+    let portion = disputed_portion(&stored_tranx, amount);
+
+    let u_account: Option<Account> = ACCOUNTS.lock().unwrap().modify(acct.key(), |acct| {
+        if let Some(acc) = acct {
+            acc.dispute(&portion);
             Some(*acc)
         } else {
             None
-        };
-        return a;
+        }
     });
 
     if let Some(acct) = u_account {
-        let dispute = Dispute::new(tranx.client, tranx.tx, false);
+        let mut dispute = stored_dispute.unwrap_or_else(|| Dispute::new(client, tranx.tx, false));
+        // a dispute that was fully settled opens again from now
+        if stored_dispute.is_none_or(|dis| dis.resolved) {
+            dispute.opened = journal::processed();
+        }
+        dispute.amount = round_up(dispute.amount + amount);
+        dispute.held = round_up(dispute.held + amount);
+        dispute.resolved = false;
 
         ACCOUNTS.lock().unwrap().insert(acct);
         // Store dispute
         transaction::DISPUTES.lock().unwrap().insert(dispute);
     }
+
+    Outcome::Applied
 }
 
-pub fn process_resolve(tranx: &Transaction) {
+/// Releases part or all of the funds held by a dispute.
+pub fn process_resolve(tranx: &Transaction) -> Outcome {
     if tranx.r#type != "resolve" {
-        return;
+        return Outcome::Rejected(Reject::UnsupportedType);
     }
 
-    let tx_exists: bool;
-    let dispute_exists: bool;
+    let tx_exists: bool = transaction::TRANSACTIONS.lock().unwrap().exists(tranx.tx);
+    let dispute_exists: bool = transaction::DISPUTES.lock().unwrap().exists(tranx.tx);
 
-    tx_exists = transaction::TRANSACTIONS.lock().unwrap().exists(tranx.tx);
-    dispute_exists = transaction::DISPUTES.lock().unwrap().exists(tranx.tx);
+    if !tx_exists {
+        return Outcome::Rejected(Reject::UnknownTx);
+    }
 
-    if !tx_exists || !dispute_exists {
-        return;
+    if !dispute_exists {
+        return Outcome::Rejected(Reject::UnknownDispute);
     }
 
     let stored_tranx: Transaction = transaction::TRANSACTIONS
@@ -234,46 +669,61 @@ pub fn process_resolve(tranx: &Transaction) {
     let stored_dispute: Dispute = transaction::DISPUTES
         .lock()
         .unwrap()
-        .read(tranx.tx, |trx| trx.unwrap().clone());
+        .read(tranx.tx, |trx| *trx.unwrap());
 
     // ignore if dispute has been resolved
     if stored_dispute.resolved {
-        return;
+        return Outcome::Rejected(Reject::DisputeResolved);
     }
 
-    let acct = get_account(tranx.client);
+    let amount = match settled_amount(tranx, &stored_dispute) {
+        Ok(amount) => amount,
+        Err(reject) => return Outcome::Rejected(reject),
+    };
+
+    let client = disputed_client(tranx, &stored_tranx);
+    // disputes apply in the currency of the original transaction
+    let acct = get_account(client, stored_tranx.currency);
 
     // ignore if account is frozen
     if acct.locked {
-        return;
+        return Outcome::Rejected(Reject::AccountLocked);
     }
 
-    let u_account: Option<Account> = ACCOUNTS.lock().unwrap().modify(tranx.client, |acct| {
-        let a = if let Some(acc) = acct {
-            acc.resolve(&stored_tranx);
+    let portion = disputed_portion(&stored_tranx, amount);
+
+    let u_account: Option<Account> = ACCOUNTS.lock().unwrap().modify(acct.key(), |acct| {
+        if let Some(acc) = acct {
+            acc.resolve(&portion);
             Some(*acc)
         } else {
             None
-        };
-        return a;
+        }
     });
 
     if let Some(acct) = u_account {
         ACCOUNTS.lock().unwrap().insert(acct);
-        resolve_dispute(stored_dispute.tx);
+        settle_dispute(stored_dispute.tx, amount);
     }
+
+    Outcome::Applied
 }
 
-pub fn process_chargeback(tranx: &Transaction) {
+/// Takes disputed funds back from the client and locks every bucket it holds.
+pub fn process_chargeback(tranx: &Transaction) -> Outcome {
     if tranx.r#type != "chargeback" {
-        return;
+        return Outcome::Rejected(Reject::UnsupportedType);
     }
 
     let tx_exists: bool = transaction::TRANSACTIONS.lock().unwrap().exists(tranx.tx);
     let dispute_exists = transaction::DISPUTES.lock().unwrap().exists(tranx.tx);
 
-    if !tx_exists || !dispute_exists {
-        return;
+    if !tx_exists {
+        return Outcome::Rejected(Reject::UnknownTx);
+    }
+
+    if !dispute_exists {
+        return Outcome::Rejected(Reject::UnknownDispute);
     }
 
     let stored_tranx: Transaction = transaction::TRANSACTIONS
@@ -284,69 +734,479 @@ pub fn process_chargeback(tranx: &Transaction) {
     let stored_dispute: Dispute = transaction::DISPUTES
         .lock()
         .unwrap()
-        .read(tranx.tx, |trx| trx.unwrap().clone());
+        .read(tranx.tx, |trx| *trx.unwrap());
 
     // ignore if dispute has been resolved
     if stored_dispute.resolved {
-        return;
+        return Outcome::Rejected(Reject::DisputeResolved);
     }
 
-    let acct = get_account(tranx.client);
+    let amount = match settled_amount(tranx, &stored_dispute) {
+        Ok(amount) => amount,
+        Err(reject) => return Outcome::Rejected(reject),
+    };
+
+    let client = disputed_client(tranx, &stored_tranx);
+    // disputes apply in the currency of the original transaction
+    let acct = get_account(client, stored_tranx.currency);
 
     // ignore if account is frozen
     if acct.locked {
-        return;
+        return Outcome::Rejected(Reject::AccountLocked);
     }
 
-    let u_account: Option<Account> = ACCOUNTS.lock().unwrap().modify(tranx.client, |acct| {
-        let a = if let Some(acc) = acct {
-            acc.chargeback(&stored_tranx);
+    let portion = disputed_portion(&stored_tranx, amount);
+
+    let mut posting: Option<Posting> = None;
+    let u_account: Option<Account> = ACCOUNTS.lock().unwrap().modify(acct.key(), |acct| {
+        if let Some(acc) = acct {
+            posting = acc.chargeback(&portion);
             Some(*acc)
         } else {
             None
-        };
-        return a;
+        }
     });
 
     if let Some(acct) = u_account {
         ACCOUNTS.lock().unwrap().insert(acct);
-        resolve_dispute(stored_dispute.tx);
+        settle_dispute(stored_dispute.tx, amount);
+        transaction::DISPUTES
+            .lock()
+            .unwrap()
+            .modify(stored_dispute.tx, |dis| {
+                let disp = dis.unwrap();
+                disp.charged_back = round_up(disp.charged_back + amount);
+            });
+
+        // the lock covers every currency the client holds
+        freeze_client(client);
+
+        // a charged back transfer goes back to the sender
+        if let Some(posting) = posting.filter(|_| stored_tranx.r#type == "transfer") {
+            ACCOUNTS
+                .lock()
+                .unwrap()
+                .modify((stored_tranx.client, stored_tranx.currency), |acct| {
+                    acct.unwrap().apply(&posting)
+                });
+        }
+    }
+
+    Outcome::Applied
+}
+
+/// Lifts the lock of a client that is not closed.
+pub fn process_unlock(tranx: &Transaction) -> Outcome {
+    if tranx.r#type != "unlock" {
+        return Outcome::Rejected(Reject::UnsupportedType);
+    }
+
+    let acct = match find_account(tranx.client) {
+        Some(acct) => acct,
+        None => return Outcome::Rejected(Reject::UnknownClient),
+    };
+
+    if acct.closed {
+        return Outcome::Rejected(Reject::AccountClosed);
+    }
+
+    if !acct.locked {
+        return Outcome::Rejected(Reject::AccountNotLocked);
+    }
+
+    for key in client_keys(tranx.client) {
+        ACCOUNTS
+            .lock()
+            .unwrap()
+            .modify(key, |acct| acct.unwrap().unlock());
+    }
+
+    Outcome::Applied
+}
+
+/// Locks every bucket of a client.
+pub fn process_freeze(tranx: &Transaction) -> Outcome {
+    if tranx.r#type != "freeze" {
+        return Outcome::Rejected(Reject::UnsupportedType);
+    }
+
+    let acct = match find_account(tranx.client) {
+        Some(acct) => acct,
+        None => return Outcome::Rejected(Reject::UnknownClient),
+    };
+
+    if acct.closed {
+        return Outcome::Rejected(Reject::AccountClosed);
+    }
+
+    if acct.locked {
+        return Outcome::Rejected(Reject::AccountLocked);
+    }
+
+    freeze_client(tranx.client);
+
+    Outcome::Applied
+}
+
+/// Closes a client with no funds held.
+pub fn process_close(tranx: &Transaction) -> Outcome {
+    if tranx.r#type != "close" {
+        return Outcome::Rejected(Reject::UnsupportedType);
+    }
+
+    let acct = match find_account(tranx.client) {
+        Some(acct) => acct,
+        None => return Outcome::Rejected(Reject::UnknownClient),
+    };
+
+    if acct.closed {
+        return Outcome::Rejected(Reject::AccountClosed);
+    }
+
+    // disputed funds have to be settled before the account can close
+    let held = client_keys(tranx.client).into_iter().any(|key| {
+        ACCOUNTS
+            .lock()
+            .unwrap()
+            .read(key, |acct| acct.unwrap().held > 0.0)
+    });
+
+    if held {
+        return Outcome::Rejected(Reject::FundsHeld);
+    }
+
+    for key in client_keys(tranx.client) {
+        ACCOUNTS
+            .lock()
+            .unwrap()
+            .modify(key, |acct| acct.unwrap().close());
+    }
+
+    Outcome::Applied
+}
+
+/// Moves funds from available to held until the authorization is captured,
+/// voided or expires.
+pub fn process_authorize(tranx: &Transaction) -> Outcome {
+    if tranx.r#type != "authorize" {
+        return Outcome::Rejected(Reject::UnsupportedType);
     }
+
+    // a negative hold would raise available funds, and capturing it would create money
+    if tranx.amount <= 0.0 {
+        return Outcome::Rejected(Reject::InvalidAmount);
+    }
+
+    // handle duplicates
+    if let Some(reject) = check_duplicate(tranx) {
+        return Outcome::Rejected(reject);
+    }
+
+    let acct = get_account(tranx.client, tranx.currency);
+
+    // ignore if account is frozen
+    if acct.locked {
+        return Outcome::Rejected(Reject::AccountLocked);
+    }
+
+    if acct.closed {
+        return Outcome::Rejected(Reject::AccountClosed);
+    }
+
+    // an authorization spends like a withdrawal, so the same limits apply
+    if let Some(reject) = limits::check(tranx) {
+        return Outcome::Rejected(reject);
+    }
+
+    if tranx.amount > acct.available {
+        return Outcome::Rejected(Reject::InsufficientFunds);
+    }
+
+    ACCOUNTS
+        .lock()
+        .unwrap()
+        .modify(acct.key(), |acct| acct.unwrap().authorize(tranx));
+
+    save_transaction(tranx);
+    authorization::open(tranx);
+
+    Outcome::Applied
 }
 
-fn get_account(client: u16) -> Account {
-    let account_exists: bool;
-    account_exists = ACCOUNTS.lock().unwrap().exists(client);
+/// Debits part or all of what is still held. A capture without an amount takes
+/// everything that is left.
+pub fn process_capture(tranx: &Transaction) -> Outcome {
+    if tranx.r#type != "capture" {
+        return Outcome::Rejected(Reject::UnsupportedType);
+    }
+
+    if tranx.amount < 0.0 {
+        return Outcome::Rejected(Reject::InvalidAmount);
+    }
+
+    let auth = match open_authorization(tranx) {
+        Ok(auth) => auth,
+        Err(reject) => return Outcome::Rejected(reject),
+    };
+
+    let amount = if tranx.amount > 0.0 {
+        tranx.amount
+    } else {
+        auth.held
+    };
+
+    if amount > auth.held {
+        return Outcome::Rejected(Reject::ExceedsAuthorized);
+    }
+
+    let acct = get_account(auth.client, auth.currency);
+
+    // ignore if account is frozen
+    if acct.locked {
+        return Outcome::Rejected(Reject::AccountLocked);
+    }
+
+    let portion = authorized_portion(&auth, amount);
+    ACCOUNTS
+        .lock()
+        .unwrap()
+        .modify(acct.key(), |acct| acct.unwrap().capture(&portion));
+
+    authorization::settle(auth.tx, amount, authorization::Status::Captured);
+
+    Outcome::Applied
+}
+
+/// Releases whatever an authorization still holds.
+pub fn process_void(tranx: &Transaction) -> Outcome {
+    if tranx.r#type != "void" {
+        return Outcome::Rejected(Reject::UnsupportedType);
+    }
+
+    if tranx.amount < 0.0 {
+        return Outcome::Rejected(Reject::InvalidAmount);
+    }
+
+    let auth = match open_authorization(tranx) {
+        Ok(auth) => auth,
+        Err(reject) => return Outcome::Rejected(reject),
+    };
+
+    let acct = get_account(auth.client, auth.currency);
+
+    // ignore if account is frozen
+    if acct.locked {
+        return Outcome::Rejected(Reject::AccountLocked);
+    }
+
+    let portion = authorized_portion(&auth, auth.held);
+    ACCOUNTS
+        .lock()
+        .unwrap()
+        .modify(acct.key(), |acct| acct.unwrap().release(&portion));
+
+    authorization::settle(auth.tx, auth.held, authorization::Status::Voided);
+
+    Outcome::Applied
+}
+
+/// Applies a transaction held for review. It is journaled as its own row,
+/// followed by the `approve` row, and skips the rules that held it.
+pub fn process_approve(tranx: &Transaction) -> Outcome {
+    if tranx.r#type != "approve" {
+        return Outcome::Rejected(Reject::UnsupportedType);
+    }
+
+    // the row stays pending until it is applied, so one rejected for lack of
+    // funds can be approved again later or declined
+    let parked = match review::find(tranx) {
+        Ok(parked) => parked,
+        Err(reject) => return Outcome::Rejected(reject),
+    };
+
+    // the parked row is not stored yet, so its counterparty is only known from the row itself
+    let before = events::snapshot(&parked);
+    let outcome = dispatch(&parked);
+
+    journal::record(&parked, outcome);
+    history::record(&parked, outcome);
+
+    if outcome.is_applied() {
+        // the row was found pending above, so deciding it cannot fail
+        let _ = review::decide(tranx, Status::Approved);
+        fee::charge(&parked);
+    }
+
+    events::publish(&parked, before);
+
+    outcome
+}
+
+/// Discards a transaction held for review without touching any balance.
+pub fn process_decline(tranx: &Transaction) -> Outcome {
+    if tranx.r#type != "decline" {
+        return Outcome::Rejected(Reject::UnsupportedType);
+    }
+
+    match review::decide(tranx, Status::Declined) {
+        Ok(_) => Outcome::Applied,
+        Err(reject) => Outcome::Rejected(reject),
+    }
+}
+
+// Tells an exact replay of a stored transaction apart from a different
+// transaction reusing its id
+fn check_duplicate(tranx: &Transaction) -> Option<Reject> {
+    transaction::TRANSACTIONS
+        .lock()
+        .unwrap()
+        .read(tranx.tx, |trx| match trx {
+            None => None,
+            Some(stored) if stored == tranx => Some(Reject::DuplicateTx),
+            Some(_) => Some(Reject::ConflictingTx),
+        })
+}
+
+// Rows kept under their own tx id, as opposed to rows referring to an earlier one
+fn stores_tx(tranx: &Transaction) -> bool {
+    matches!(
+        tranx.r#type.as_str(),
+        "deposit" | "withdrawal" | "transfer" | "convert" | "authorize"
+    )
+}
+
+// The authorization a capture or void refers to, if it is the client's and still open
+fn open_authorization(tranx: &Transaction) -> Result<authorization::Authorization, Reject> {
+    match authorization::find(tranx.tx) {
+        Some(auth) if auth.client == tranx.client => match auth.status {
+            authorization::Status::Open => Ok(auth),
+            _ => Err(Reject::AuthorizationClosed),
+        },
+        _ => Err(Reject::UnknownAuthorization),
+    }
+}
+
+// The slice of an authorization that a capture or void acts on
+fn authorized_portion(auth: &authorization::Authorization, amount: f32) -> Transaction {
+    let mut portion = Transaction::new("authorize".to_string(), auth.client, auth.tx, amount);
+    portion.currency = auth.currency;
+
+    portion
+}
+
+/// The client whose funds a dispute, resolve or chargeback acts on: the
+/// receiver of a transfer, the client on the row otherwise.
+pub fn disputed_client(tranx: &Transaction, stored_tranx: &Transaction) -> ClientId {
+    match stored_tranx.to_client {
+        Some(to_client) if stored_tranx.r#type == "transfer" => to_client,
+        _ => tranx.client,
+    }
+}
+
+// The slice of a stored transaction that a dispute, resolve or chargeback acts on
+fn disputed_portion(stored_tranx: &Transaction, amount: f32) -> Transaction {
+    let mut portion = stored_tranx.clone();
+    portion.amount = amount;
+
+    portion
+}
+
+// Resolves and chargebacks without an amount settle everything still held
+fn settled_amount(tranx: &Transaction, dispute: &Dispute) -> Result<f32, Reject> {
+    if tranx.amount < 0.0 {
+        return Err(Reject::InvalidAmount);
+    }
+
+    if tranx.amount == 0.0 {
+        return Ok(dispute.held);
+    }
+
+    if tranx.amount > dispute.held {
+        return Err(Reject::ExceedsDisputed);
+    }
+
+    Ok(tranx.amount)
+}
+
+/// Keys of every currency bucket held by a client.
+pub fn client_keys(client: ClientId) -> Vec<AccountKey> {
+    CURRENCIES
+        .lock()
+        .unwrap()
+        .get(&client)
+        .map_or(vec![], |currencies| {
+            currencies
+                .iter()
+                .map(|currency| (client, *currency))
+                .collect()
+        })
+}
+
+/// Locks every currency bucket of a client.
+pub fn freeze_client(client: ClientId) {
+    for key in client_keys(client) {
+        ACCOUNTS
+            .lock()
+            .unwrap()
+            .modify(key, |acct| acct.unwrap().freeze());
+    }
+}
+
+fn find_account(client: ClientId) -> Option<Account> {
+    let currency = *CURRENCIES.lock().unwrap().get(&client)?.first()?;
+
+    ACCOUNTS
+        .lock()
+        .unwrap()
+        .read((client, currency), |acct| acct.copied())
+}
+
+/// Returns the balances of a client in a currency, opening an empty bucket
+/// if the client has none yet.
+pub fn get_account(client: ClientId, currency: Currency) -> Account {
+    let account_exists: bool = ACCOUNTS.lock().unwrap().exists((client, currency));
 
     if !account_exists {
-        let new_account = Account::new(client, 0.0, 0.0);
+        let mut new_account = Account::new(client, 0.0, 0.0);
+        new_account.currency = currency;
+
+        // a new currency bucket shares the lock and closed state of the client
+        if let Some(acct) = find_account(client) {
+            new_account.locked = acct.locked;
+            new_account.closed = acct.closed;
+        }
+
         ACCOUNTS.lock().unwrap().insert(new_account);
-        CLIENTS.lock().unwrap().push(new_account.client);
+        CLIENTS.lock().unwrap().push(new_account.key());
+        CURRENCIES
+            .lock()
+            .unwrap()
+            .entry(client)
+            .or_default()
+            .push(currency);
     }
 
     ACCOUNTS
         .lock()
         .unwrap()
-        .read(client, |acct| acct.unwrap().clone())
+        .read((client, currency), |acct| *acct.unwrap())
 }
 
 fn save_transaction(tranx: &Transaction) {
-    let new_tranx = Transaction::new(
-        tranx.r#type.to_string(),
-        tranx.client,
-        tranx.tx,
-        tranx.amount,
-    );
-
-    transaction::TRANSACTIONS.lock().unwrap().insert(new_tranx);
+    transaction::TRANSACTIONS
+        .lock()
+        .unwrap()
+        .insert(tranx.clone());
 }
 
-fn resolve_dispute(tx: u32) {
+// Settles part or all of the held amount of a dispute
+fn settle_dispute(tx: TxId, amount: f32) {
     let updated_dispute: Dispute = transaction::DISPUTES.lock().unwrap().modify(tx, |dis| {
         let disp = dis.unwrap();
-        disp.resolved = true;
+        disp.held = round_up(disp.held - amount);
+        disp.resolved = disp.held <= 0.0;
 
-        return *disp;
+        *disp
     });
 
     transaction::DISPUTES
@@ -0,0 +1,365 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+
+use super::account::{round_up, ACCOUNTS};
+use super::events;
+use super::history::{self, Window};
+use super::outcome::Outcome;
+use crate::storage::{Storage, StoreKey};
+use crate::tx::currency::Currency;
+use crate::tx::id::{ClientId, TxId};
+use crate::tx::journal;
+use crate::tx::transaction::Transaction;
+
+/// Card-style holds, kept apart from disputes.
+pub static AUTHORIZATIONS: Lazy<Mutex<Storage<TxId, Authorization>>> =
+    Lazy::new(|| Mutex::new(Storage::new()));
+
+// open authorizations by the time they were made, oldest first
+type OpenSet = BTreeSet<(DateTime<Utc>, TxId)>;
+
+static OPEN: Lazy<Mutex<OpenSet>> = Lazy::new(|| Mutex::new(BTreeSet::new()));
+
+// how long an authorization may stay open; forever when unset
+static EXPIRY: Lazy<Mutex<Option<TimeDelta>>> = Lazy::new(|| Mutex::new(None));
+
+static CLOCK: Lazy<Mutex<Clock>> = Lazy::new(|| Mutex::new(Clock::Rows));
+
+// the latest time expiry was checked at, so a sweep at the end of a run
+// does not have to guess one
+static LATEST: Lazy<Mutex<Option<DateTime<Utc>>>> = Lazy::new(|| Mutex::new(None));
+
+/// The clock authorizations are opened and expired by. One run uses one
+/// clock, so an authorization is never timed on one and expired on the other.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Clock {
+    /// Row timestamps. A row without one opens a hold that never expires and
+    /// expires none itself.
+    Rows,
+    /// The time a row is processed, for services whose clients may leave
+    /// timestamps out.
+    Wall,
+}
+
+/// Where an authorization is in its life.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// Still holding funds.
+    Open,
+    /// Fully captured.
+    Captured,
+    /// Released by a `void` row.
+    Voided,
+    /// Released once open for the configured expiry.
+    Expired,
+}
+
+/// Funds held for a card-style payment until it is captured, voided or expires.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub struct Authorization {
+    /// The client whose funds are held.
+    pub client: ClientId,
+    /// The tx id of the `authorize` row.
+    pub tx: TxId,
+    /// The currency of the held funds.
+    pub currency: Currency,
+    /// The amount originally authorized.
+    pub amount: f32,
+    /// Amount still held, reduced by partial captures.
+    pub held: f32,
+    /// Amount captured so far.
+    pub captured: f32,
+    /// When the authorization was made, used for expiry.
+    pub authorized: DateTime<Utc>,
+    /// Open until captured in full, voided or expired.
+    pub status: Status,
+}
+
+impl StoreKey for Authorization {
+    type Key = TxId;
+
+    fn key(&self) -> Self::Key {
+        self.tx
+    }
+}
+
+/// Accepts durations like `30m`, `12h` or `7d`.
+pub fn set_expiry(expiry: &str) -> Result<(), String> {
+    match Window::parse(expiry)? {
+        Window::Duration(span) => {
+            *EXPIRY.lock().unwrap() = Some(span);
+            Ok(())
+        }
+        Window::Transactions(_) => Err(format!(
+            "expected a duration like 7d for the authorization expiry, got {}",
+            expiry
+        )),
+    }
+}
+
+/// Picks the clock expiry follows. Rows are the default.
+pub fn set_clock(clock: Clock) {
+    *CLOCK.lock().unwrap() = clock;
+}
+
+/// When `tranx` happens on the expiry clock, if it has a time on it.
+pub fn clock_time(tranx: &Transaction) -> Option<DateTime<Utc>> {
+    match *CLOCK.lock().unwrap() {
+        Clock::Rows => tranx.timestamp,
+        Clock::Wall => Some(Utc::now()),
+    }
+}
+
+/// Records an applied `authorize` row as an open authorization.
+pub fn open(tranx: &Transaction) {
+    let timed = clock_time(tranx);
+    // untimed holds are still listed in order with the rest
+    let authorized = timed.unwrap_or_else(|| history::time_of(tranx));
+
+    AUTHORIZATIONS.lock().unwrap().insert(Authorization {
+        client: tranx.client,
+        tx: tranx.tx,
+        currency: tranx.currency,
+        amount: tranx.amount,
+        held: tranx.amount,
+        captured: 0.0,
+        authorized,
+        status: Status::Open,
+    });
+
+    // only a hold with a time on the expiry clock can run out
+    if timed.is_some() {
+        OPEN.lock().unwrap().insert((authorized, tranx.tx));
+    }
+}
+
+/// The authorization made under `tx`, if any.
+pub fn find(tx: TxId) -> Option<Authorization> {
+    AUTHORIZATIONS
+        .lock()
+        .unwrap()
+        .read(tx, |auth| auth.copied())
+}
+
+/// Takes `amount` off the hold. A hold that is used up, or released by a void
+/// or expiry, closes with `status`.
+pub fn settle(tx: TxId, amount: f32, status: Status) {
+    let auth: Authorization = AUTHORIZATIONS.lock().unwrap().modify(tx, |auth| {
+        let auth = auth.unwrap();
+        auth.held = round_up(auth.held - amount);
+
+        if status == Status::Captured {
+            auth.captured = round_up(auth.captured + amount);
+        }
+
+        if status != Status::Captured || auth.held <= 0.0 {
+            auth.status = status;
+        }
+
+        *auth
+    });
+
+    if auth.status != Status::Open {
+        OPEN.lock().unwrap().remove(&(auth.authorized, tx));
+    }
+}
+
+/// Releases every authorization that has been open longer than the configured expiry.
+pub fn expire(now: DateTime<Utc>) -> usize {
+    let mut latest = LATEST.lock().unwrap();
+    if latest.is_none_or(|latest| latest < now) {
+        *latest = Some(now);
+    }
+    drop(latest);
+
+    match *EXPIRY.lock().unwrap() {
+        Some(expiry) => expire_after(now, expiry),
+        None => 0,
+    }
+}
+
+/// Releases what ran out by the end of a run: by now on the wall clock, or by
+/// the latest row time seen on the row clock.
+pub fn sweep() -> usize {
+    let now = match *CLOCK.lock().unwrap() {
+        Clock::Rows => *LATEST.lock().unwrap(),
+        Clock::Wall => Some(Utc::now()),
+    };
+
+    now.map_or(0, expire)
+}
+
+/// Releases every authorization open for at least `expiry` at `now`, and returns how many there were.
+pub fn expire_after(now: DateTime<Utc>, expiry: TimeDelta) -> usize {
+    let due: Vec<TxId> = OPEN
+        .lock()
+        .unwrap()
+        .iter()
+        .take_while(|(authorized, _)| *authorized + expiry <= now)
+        .map(|(_, tx)| *tx)
+        .collect();
+
+    for tx in due.iter() {
+        let auth = find(*tx).unwrap();
+
+        // journaled as its own row so the release shows up in reports
+        let mut release = Transaction::new("expire".to_string(), auth.client, auth.tx, auth.held);
+        release.currency = auth.currency;
+
+        let before = events::snapshot(&release);
+        ACCOUNTS
+            .lock()
+            .unwrap()
+            .modify((auth.client, auth.currency), |acct| {
+                acct.unwrap().release(&release)
+            });
+        settle(auth.tx, auth.held, Status::Expired);
+
+        journal::record(&release, Outcome::Applied);
+        events::publish(&release, before);
+    }
+
+    due.len()
+}
+
+/// Authorizations of a client, oldest first.
+pub fn client_authorizations(client: ClientId) -> Vec<Authorization> {
+    let mut auths: Vec<Authorization> = AUTHORIZATIONS.lock().unwrap().reads(|auths| {
+        auths
+            .map(|(_, auth)| *auth)
+            .filter(|auth| auth.client == client)
+            .collect()
+    });
+    auths.sort_by_key(|auth| (auth.authorized, auth.tx));
+
+    auths
+}
+
+/// Every authorization, for checks that rebuild balances.
+pub fn all() -> Vec<Authorization> {
+    AUTHORIZATIONS
+        .lock()
+        .unwrap()
+        .reads(|auths| auths.map(|(_, auth)| *auth).collect())
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::{client_authorizations, expire_after, Status};
+    use crate::ac::account;
+    use crate::ac::invariant;
+    use crate::ac::outcome::{Outcome, Reject};
+    use crate::tx::currency::Currency;
+    use crate::tx::id::{ClientId, TxId};
+    use crate::tx::timestamp;
+    use crate::tx::transaction::Transaction;
+    use chrono::TimeDelta;
+
+    fn tranx(r#type: &str, client: u64, tx: u64, amount: f32) -> Transaction {
+        Transaction::new(r#type.to_string(), ClientId(client), TxId(tx), amount)
+    }
+
+    #[test]
+    fn test_authorize_capture_void() {
+        let rows = [
+            tranx("deposit", 197, 1970, 100.0),
+            tranx("authorize", 197, 1971, 60.0),
+            tranx("authorize", 197, 1972, 50.0),
+            tranx("capture", 197, 1971, 20.0),
+            tranx("capture", 197, 1971, 50.0),
+            tranx("capture", 197, 1971, 0.0),
+            tranx("void", 197, 1971, 0.0),
+            tranx("authorize", 197, 1973, 30.0),
+            tranx("void", 197, 1973, 0.0),
+            tranx("dispute", 197, 1973, 0.0),
+        ];
+
+        let outcomes: Vec<Outcome> = rows.iter().map(account::process).collect();
+
+        assert!(
+            outcomes
+                == [
+                    Outcome::Applied,
+                    Outcome::Applied,
+                    Outcome::Rejected(Reject::InsufficientFunds),
+                    Outcome::Applied,
+                    Outcome::Rejected(Reject::ExceedsAuthorized),
+                    Outcome::Applied,
+                    Outcome::Rejected(Reject::AuthorizationClosed),
+                    Outcome::Applied,
+                    Outcome::Applied,
+                    Outcome::Rejected(Reject::NotDisputable),
+                ],
+            "wrong outcomes; got {:?}",
+            outcomes
+        );
+
+        let acct = account::get_account(ClientId(197), Currency::default());
+        assert!(
+            acct.available == 40.0 && acct.held == 0.0 && acct.total == 40.0,
+            "wrong balances; expected {} available, got {:?}",
+            40.0,
+            acct
+        );
+
+        let statuses: Vec<Status> = client_authorizations(ClientId(197))
+            .iter()
+            .map(|auth| auth.status)
+            .collect();
+        assert!(
+            statuses == [Status::Captured, Status::Voided],
+            "wrong authorization statuses; got {:?}",
+            statuses
+        );
+
+        let checked = invariant::check_after(&rows[8]);
+        assert!(
+            checked.is_ok(),
+            "invariants should hold; got {}",
+            checked.unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_authorization_expiry() {
+        let mut deposit = tranx("deposit", 198, 1980, 10.0);
+        let mut authorize = tranx("authorize", 198, 1981, 4.0);
+        deposit.timestamp = Some(timestamp::parse("2024-01-01T09:00:00Z").unwrap());
+        authorize.timestamp = Some(timestamp::parse("2024-01-01T10:00:00Z").unwrap());
+
+        account::process(&deposit);
+        account::process(&authorize);
+
+        let hour = TimeDelta::hours(1);
+        let early = timestamp::parse("2024-01-01T10:30:00Z").unwrap();
+        let late = timestamp::parse("2024-01-01T11:00:00Z").unwrap();
+
+        expire_after(early, hour);
+        let acct = account::get_account(ClientId(198), Currency::default());
+        assert!(
+            acct.held == 4.0,
+            "authorization should still be held; got {:?}",
+            acct
+        );
+
+        expire_after(late, hour);
+        let acct = account::get_account(ClientId(198), Currency::default());
+        assert!(
+            acct.available == 10.0 && acct.held == 0.0,
+            "expired authorization should be released; got {:?}",
+            acct
+        );
+
+        let capture = account::process(&tranx("capture", 198, 1981, 0.0));
+        assert!(
+            capture == Outcome::Rejected(Reject::AuthorizationClosed),
+            "expired authorization cannot be captured; got {:?}",
+            capture
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use serde::Serialize;
+use std::ffi::OsString;
+use std::fs::File;
+use std::path::Path;
+
+use crate::error::Error;
+use crate::tx::currency::Currency;
+use crate::tx::id::{ClientId, TxId};
+use crate::tx::journal;
+use crate::tx::transaction::{self, Dispute, Transaction};
+
+/// A dispute that still holds funds at the end of a run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OpenDispute {
+    /// The client whose funds are held.
+    pub client: ClientId,
+    /// The tx id of the disputed transaction.
+    pub tx: TxId,
+    /// The type of the disputed transaction.
+    pub r#type: String,
+    /// The currency the funds are held in.
+    pub currency: Currency,
+    /// Amount of the original transaction.
+    pub amount: f32,
+    /// Amount still held under dispute.
+    pub held: f32,
+    /// Transactions processed since the dispute was opened.
+    pub age: usize,
+}
+
+/// Every unresolved dispute, oldest first.
+pub fn open() -> Vec<OpenDispute> {
+    open_at(journal::processed())
+}
+
+/// Open disputes aged as if `processed` transactions had been processed so far.
+pub fn open_at(processed: usize) -> Vec<OpenDispute> {
+    let disputes: Vec<Dispute> = transaction::DISPUTES.lock().unwrap().reads(|disputes| {
+        disputes
+            .filter(|(_, dis)| !dis.resolved)
+            .map(|(_, dis)| *dis)
+            .collect()
+    });
+
+    let mut open: Vec<OpenDispute> = disputes
+        .into_iter()
+        .filter_map(|dispute| {
+            let stored: Transaction = transaction::TRANSACTIONS
+                .lock()
+                .unwrap()
+                .read(dispute.tx, |trx| trx.cloned())?;
+
+            Some(OpenDispute {
+                client: dispute.client,
+                tx: dispute.tx,
+                r#type: stored.r#type,
+                currency: stored.currency,
+                amount: stored.amount,
+                held: dispute.held,
+                age: processed.saturating_sub(dispute.opened + 1),
+            })
+        })
+        .collect();
+    open.sort_by_key(|dispute| (std::cmp::Reverse(dispute.age), dispute.tx));
+
+    open
+}
+
+/// Writes the open disputes as JSON when the path ends in `.json`, as CSV otherwise.
+pub fn export(path: &OsString) -> Result<(), Error> {
+    let open = open();
+
+    let is_json = Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+
+    if is_json {
+        serde_json::to_writer_pretty(File::create(path)?, &open)?;
+        return Ok(());
+    }
+
+    let mut csv_writer = csv::Writer::from_path(path)?;
+    for dispute in open {
+        csv_writer.serialize(dispute)?;
+    }
+    csv_writer.flush()?;
+
+    Ok(())
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::open_at;
+    use crate::ac::account;
+    use crate::tx::id::{ClientId, TxId};
+    use crate::tx::journal::{self, Entry};
+    use crate::tx::transaction::{self, Transaction};
+
+    #[test]
+    fn test_open_disputes() {
+        let client = ClientId(160);
+        let rows = vec![
+            Transaction::new("deposit".to_string(), client, TxId(1600), 10.0),
+            Transaction::new("deposit".to_string(), client, TxId(1601), 5.0),
+            Transaction::new("dispute".to_string(), client, TxId(1600), 4.0),
+            Transaction::new("dispute".to_string(), client, TxId(1601), 0.0),
+            Transaction::new("resolve".to_string(), client, TxId(1601), 0.0),
+            Transaction::new("deposit".to_string(), client, TxId(1602), 1.0),
+        ];
+
+        for tranx in rows {
+            account::process(&tranx);
+        }
+
+        // other tests share the journal, so look at one copy of it
+        let entries: Vec<Entry> = journal::JOURNAL
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|entry| entry.r#type != "fee")
+            .cloned()
+            .collect();
+        let opened = transaction::DISPUTES
+            .lock()
+            .unwrap()
+            .read(TxId(1600), |dis| dis.unwrap().opened);
+
+        // the dispute itself, then the second dispute, the resolve and the last deposit
+        let later = entries[opened..]
+            .iter()
+            .filter(|entry| entry.client == client)
+            .count();
+        assert!(
+            later == 4,
+            "wrong rows after the dispute was opened; expected {}, got {}",
+            4,
+            later
+        );
+
+        let disputes: Vec<_> = open_at(entries.len())
+            .into_iter()
+            .filter(|dispute| dispute.client == client)
+            .collect();
+
+        assert!(
+            disputes.len() == 1 && disputes[0].tx == TxId(1600),
+            "only the unresolved dispute should be open; got {:?}",
+            disputes
+        );
+
+        let dispute = &disputes[0];
+        assert!(
+            dispute.amount == 10.0 && dispute.held == 4.0 && dispute.r#type == "deposit",
+            "wrong open dispute; got {:?}",
+            dispute
+        );
+
+        // aged right after the dispute row, and after the three rows that follow it
+        for (processed, age) in [(opened + 1, 0), (opened + 4, 3)] {
+            let aged: Vec<usize> = open_at(processed)
+                .into_iter()
+                .filter(|dispute| dispute.client == client)
+                .map(|dispute| dispute.age)
+                .collect();
+
+            assert!(
+                aged == [age],
+                "wrong dispute age; expected {}, got {:?}",
+                age,
+                aged
+            );
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use async_stream::stream;
+use futures_util::Stream;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::sync::Mutex;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use super::account::{self, Account, AccountKey, ACCOUNTS};
+use crate::tx::currency::Currency;
+use crate::tx::id::{ClientId, TxId};
+use crate::tx::transaction::{self, Transaction};
+
+/// How many events a subscriber may fall behind before it misses the oldest ones.
+pub const CAPACITY: usize = 4096;
+
+static CHANNEL: Lazy<broadcast::Sender<AccountChanged>> =
+    Lazy::new(|| broadcast::channel(CAPACITY).0);
+
+// optional JSONL file every event is written to as it happens
+static OUTPUT: Lazy<Mutex<Option<LineWriter<File>>>> = Lazy::new(|| Mutex::new(None));
+
+// the first failed write to the file, after which nothing more is written
+static FAILED: Lazy<Mutex<Option<io::Error>>> = Lazy::new(|| Mutex::new(None));
+
+/// A balance bucket that changed while processing one transaction. `old` is
+/// empty when the transaction opened the bucket. Fees charged on the
+/// transaction are part of the same change.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AccountChanged {
+    /// The tx id of the row that caused the change.
+    pub tx: TxId,
+    /// The type of that row.
+    pub r#type: String,
+    /// The client owning the bucket.
+    pub client: ClientId,
+    /// The currency of the bucket.
+    pub currency: Currency,
+    /// The bucket before the row.
+    pub old: Option<Account>,
+    /// The bucket after the row.
+    pub new: Account,
+}
+
+/// How many events a subscriber missed by falling more than [`CAPACITY`] behind.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Missed(pub u64);
+
+/// Balances of the buckets a transaction may touch, taken before it is processed.
+pub struct Snapshot {
+    clients: Vec<ClientId>,
+    accounts: HashMap<AccountKey, Account>,
+}
+
+/// Events published from now on, in the order they happened. A subscriber
+/// that falls behind gets [`Missed`] in place of the events it lost.
+pub fn subscribe() -> impl Stream<Item = Result<AccountChanged, Missed>> {
+    let mut receiver = CHANNEL.subscribe();
+
+    stream! {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => yield Ok(event),
+                Err(RecvError::Lagged(missed)) => yield Err(Missed(missed)),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+}
+
+/// Writes every following event to `path`, one JSON object per line.
+pub fn write_to(path: &OsString) -> io::Result<()> {
+    let file = File::create(path)?;
+    *OUTPUT.lock().unwrap() = Some(LineWriter::new(file));
+
+    Ok(())
+}
+
+/// Stops writing events to the file, flushing it. Fails with the first write
+/// that failed while processing, since the file is incomplete from there on.
+pub fn close() -> io::Result<()> {
+    if let Some(mut writer) = OUTPUT.lock().unwrap().take() {
+        writer.flush()?;
+    }
+
+    match FAILED.lock().unwrap().take() {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Takes the balances a transaction may change, so they can be compared
+/// after it is processed. Nothing is captured while no one is listening.
+pub fn snapshot(tranx: &Transaction) -> Option<Snapshot> {
+    if CHANNEL.receiver_count() == 0 && OUTPUT.lock().unwrap().is_none() {
+        return None;
+    }
+
+    let clients = involved(tranx);
+    let accounts = read_accounts(&clients);
+
+    Some(Snapshot { clients, accounts })
+}
+
+/// Publishes one event for every bucket that differs from the snapshot.
+pub fn publish(tranx: &Transaction, before: Option<Snapshot>) {
+    let before = match before {
+        Some(before) => before,
+        None => return,
+    };
+
+    let after = read_accounts(&before.clients);
+    let mut changed: Vec<AccountChanged> = after
+        .into_iter()
+        .filter(|(key, new)| before.accounts.get(key) != Some(new))
+        .map(|(key, new)| AccountChanged {
+            tx: tranx.tx,
+            r#type: tranx.r#type.to_string(),
+            client: key.0,
+            currency: key.1,
+            old: before.accounts.get(&key).copied(),
+            new,
+        })
+        .collect();
+    changed.sort_by_key(|event| (event.client, event.currency.to_string()));
+
+    let mut output = OUTPUT.lock().unwrap();
+    for event in changed {
+        if let Some(writer) = output.as_mut() {
+            let written = serde_json::to_string(&event)
+                .map_err(io::Error::other)
+                .and_then(|line| writeln!(writer, "{}", line));
+
+            if let Err(err) = written {
+                FAILED.lock().unwrap().get_or_insert(err);
+                *output = None;
+            }
+        }
+
+        // sending only fails when there are no subscribers
+        let _ = CHANNEL.send(event);
+    }
+}
+
+// The client of the row, its counterparty and the parties of the transaction
+// it refers to, as disputes on a transfer act on the receiving account
+fn involved(tranx: &Transaction) -> Vec<ClientId> {
+    let mut clients = vec![tranx.client];
+    clients.extend(tranx.to_client);
+
+    let stored = transaction::TRANSACTIONS
+        .lock()
+        .unwrap()
+        .read(tranx.tx, |trx| trx.map(|trx| (trx.client, trx.to_client)));
+
+    if let Some((client, to_client)) = stored {
+        clients.push(client);
+        clients.extend(to_client);
+    }
+
+    clients.sort();
+    clients.dedup();
+
+    clients
+}
+
+fn read_accounts(clients: &[ClientId]) -> HashMap<AccountKey, Account> {
+    clients
+        .iter()
+        .flat_map(|client| account::client_keys(*client))
+        .filter_map(|key| {
+            ACCOUNTS
+                .lock()
+                .unwrap()
+                .read(key, |acct| acct.copied())
+                .map(|acct| (key, acct))
+        })
+        .collect()
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::{subscribe, Missed, CAPACITY, CHANNEL};
+    use crate::ac::{account, review};
+    use crate::tx::id::{ClientId, TxId};
+    use crate::tx::transaction::Transaction;
+    use futures_util::{pin_mut, FutureExt, StreamExt};
+
+    #[test]
+    fn test_account_changed_events() {
+        let mut receiver = CHANNEL.subscribe();
+
+        let deposit = Transaction::new("deposit".to_string(), ClientId(170), TxId(1700), 8.0);
+        let withdrawal = Transaction::new("withdrawal".to_string(), ClientId(170), TxId(1701), 3.0);
+        let rejected = Transaction::new("withdrawal".to_string(), ClientId(170), TxId(1702), 9.0);
+
+        account::process(&deposit);
+        account::process(&withdrawal);
+        account::process(&rejected);
+
+        let mut events = vec![];
+        while let Ok(event) = receiver.try_recv() {
+            if event.client == ClientId(170) {
+                events.push(event);
+            }
+        }
+
+        assert!(
+            events.len() == 2,
+            "expected one event per applied row; got {:?}",
+            events
+        );
+
+        assert!(
+            events[0].tx == TxId(1700) && events[0].old.is_none() && events[0].new.available == 8.0,
+            "deposit should open the account; got {:?}",
+            events[0]
+        );
+
+        let old = events[1].old.unwrap();
+        assert!(
+            events[1].tx == TxId(1701) && old.available == 8.0 && events[1].new.available == 5.0,
+            "wrong balances on withdrawal; expected {} -> {}, got {:?}",
+            8.0,
+            5.0,
+            events[1]
+        );
+    }
+
+    #[test]
+    fn test_approved_transfer_events() {
+        let deposit = Transaction::new("deposit".to_string(), ClientId(210), TxId(2100), 20.0);
+        let mut transfer = Transaction::new("transfer".to_string(), ClientId(210), TxId(2101), 5.0);
+        transfer.to_client = Some(ClientId(211));
+        let approve = Transaction::new("approve".to_string(), ClientId(210), TxId(2101), 0.0);
+
+        account::process(&deposit);
+        review::park(&transfer, "large transfer");
+
+        let mut receiver = CHANNEL.subscribe();
+        account::process(&approve);
+
+        let mut events = vec![];
+        while let Ok(event) = receiver.try_recv() {
+            if [ClientId(210), ClientId(211)].contains(&event.client) {
+                events.push(event);
+            }
+        }
+
+        assert!(
+            events.len() == 2,
+            "expected an event for both sides of the transfer; got {:?}",
+            events
+        );
+
+        assert!(
+            events
+                .iter()
+                .all(|event| event.tx == TxId(2101) && event.r#type == "transfer"),
+            "events should carry the released row; got {:?}",
+            events
+        );
+
+        assert!(
+            events[1].client == ClientId(211)
+                && events[1].old.is_none()
+                && events[1].new.available == 5.0,
+            "wrong recipient balance; expected {}, got {:?}",
+            5.0,
+            events[1]
+        );
+    }
+
+    #[test]
+    fn test_subscriber_missed_events() {
+        let events = subscribe();
+        pin_mut!(events);
+
+        for i in 0..=CAPACITY as u64 {
+            let deposit =
+                Transaction::new("deposit".to_string(), ClientId(171), TxId(17_000 + i), 1.0);
+            account::process(&deposit);
+        }
+
+        let first = events.next().now_or_never().flatten();
+        assert!(
+            matches!(first, Some(Err(Missed(missed))) if missed > 0),
+            "a subscriber that fell behind should be told; got {:?}",
+            first
+        );
+    }
+}
@@ -7,7 +7,7 @@ use std::thread;
 
 use super::account;
 
-static mut THREADS: Lazy<Mutex<i32>> = Lazy::new(|| Mutex::new(0));
+static THREADS: Lazy<Mutex<i32>> = Lazy::new(|| Mutex::new(0));
 
 static MAX_THREAD: i32 = 100;
 
@@ -24,7 +24,7 @@ pub fn run() {
         return;
     }
     // print the headers
-    println!("client,available,held,total,locked");
+    println!("client,currency,available,held,total,locked,closed,updated");
 
     if len < max_thread {
         export(data);
@@ -63,7 +63,7 @@ pub fn run() {
     }
 }
 
-fn export(data: Vec<u16>) {
+fn export(data: Vec<account::AccountKey>) {
     let mut csv_writer = csv::WriterBuilder::new()
         .has_headers(false)
         .from_writer(io::stdout());
@@ -71,31 +71,22 @@ fn export(data: Vec<u16>) {
         let acct = account::ACCOUNTS
             .lock()
             .unwrap()
-            .read(id, |acc| acc.unwrap().clone());
+            .read(id, |acc| *acc.unwrap());
         csv_writer.serialize(acct).unwrap();
     }
     decrease_threads();
 }
 
 fn increase_threads() {
-    unsafe {
-        let mut t = THREADS.lock().unwrap();
-        *t += 1;
-    }
+    let mut t = THREADS.lock().unwrap();
+    *t += 1;
 }
 
 fn decrease_threads() {
-    unsafe {
-        let mut t = THREADS.lock().unwrap();
-        *t -= 1;
-    }
+    let mut t = THREADS.lock().unwrap();
+    *t -= 1;
 }
 
 fn get_thread_count() -> i32 {
-    let t: i32;
-    unsafe {
-        t = *THREADS.lock().unwrap();
-    }
-
-    t
+    *THREADS.lock().unwrap()
 }
@@ -0,0 +1,289 @@
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io;
+use std::sync::Mutex;
+
+use super::account::{self, round_up, ACCOUNTS};
+use super::outcome::{Outcome, Reject};
+use crate::error::Error;
+use crate::storage::{Storage, StoreKey};
+use crate::tx::journal;
+use crate::tx::transaction::{self, Transaction};
+
+/// The loaded fee schedule, keyed by transaction type.
+pub static FEES: Lazy<Mutex<Storage<String, Fee>>> = Lazy::new(|| Mutex::new(Storage::new()));
+
+/// How the amount of a fee is read.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeeKind {
+    /// A fixed amount per transaction.
+    Flat,
+    /// A percentage of the transaction amount.
+    Percent,
+}
+
+/// A fee charged on one transaction type, e.g. `withdrawal,percent,1.5`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Fee {
+    /// The transaction type the fee is charged on.
+    pub r#type: String,
+    /// Whether `amount` is flat or a percentage.
+    pub kind: FeeKind,
+    /// The flat amount, or the percentage.
+    pub amount: f32,
+}
+
+impl Fee {
+    /// The fee due on a transaction of `base`.
+    pub fn compute(&self, base: f32) -> f32 {
+        match self.kind {
+            FeeKind::Flat => self.amount,
+            FeeKind::Percent => round_up(base * self.amount / 100.0),
+        }
+    }
+}
+
+impl StoreKey for Fee {
+    type Key = String;
+
+    fn key(&self) -> Self::Key {
+        self.r#type.to_string()
+    }
+}
+
+/// Loads a fee schedule CSV. Nothing is loaded if any row is invalid.
+pub fn load(path: &OsString) -> Result<(), Error> {
+    let fees = read(File::open(path)?)?;
+
+    let store = FEES.lock().unwrap();
+    for fee in fees {
+        store.insert(fee);
+    }
+
+    Ok(())
+}
+
+/// Reads a whole fee schedule, so that a bad row leaves no fee loaded.
+pub fn read<R: io::Read>(reader: R) -> Result<Vec<Fee>, Error> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+
+    let mut fees = vec![];
+    for result in rdr.deserialize() {
+        let fee: Fee = result?;
+        if !matches!(fee.r#type.as_str(), "withdrawal" | "dispute" | "chargeback") {
+            return Err(Error::Invalid(format!(
+                "fees are not supported on {}",
+                fee.r#type
+            )));
+        }
+
+        // a negative fee would pay the client on every transaction
+        if fee.amount < 0.0 || (fee.kind == FeeKind::Percent && fee.amount > 100.0) {
+            return Err(Error::Invalid(format!(
+                "invalid fee on {}: {}",
+                fee.r#type, fee.amount
+            )));
+        }
+
+        fees.push(fee);
+    }
+
+    Ok(fees)
+}
+
+/// Charges the scheduled fee, if any, for an applied transaction.
+pub fn charge(tranx: &Transaction) -> Option<Outcome> {
+    let fee = FEES
+        .lock()
+        .unwrap()
+        .read(tranx.r#type.to_string(), |fee| fee.cloned())?;
+
+    Some(apply(&fee, tranx))
+}
+
+/// Charges `fee` on an applied transaction and journals it as a `fee` row.
+pub fn apply(fee: &Fee, tranx: &Transaction) -> Outcome {
+    let stored_tranx: Option<Transaction> = transaction::TRANSACTIONS
+        .lock()
+        .unwrap()
+        .read(tranx.tx, |trx| trx.cloned());
+
+    // full disputes and chargebacks carry no amount, the fee is based on the disputed transaction
+    let base = match &stored_tranx {
+        Some(trx) if tranx.r#type != "withdrawal" && tranx.amount <= 0.0 => trx.amount,
+        _ => tranx.amount,
+    };
+
+    // fees are charged in the currency of the disputed transaction
+    let currency = match &stored_tranx {
+        Some(trx) if tranx.r#type != "withdrawal" => trx.currency,
+        _ => tranx.currency,
+    };
+
+    // a disputed transfer holds the receiver's funds, so the receiver pays
+    let client = match &stored_tranx {
+        Some(trx) if tranx.r#type != "withdrawal" => account::disputed_client(tranx, trx),
+        _ => tranx.client,
+    };
+
+    let mut fee_tranx = Transaction::new("fee".to_string(), client, tranx.tx, fee.compute(base));
+    fee_tranx.currency = currency;
+
+    let acct = account::get_account(client, currency);
+    let outcome = if fee_tranx.amount <= acct.available {
+        ACCOUNTS
+            .lock()
+            .unwrap()
+            .modify(acct.key(), |acct| acct.unwrap().charge_fee(&fee_tranx));
+        Outcome::Applied
+    } else {
+        Outcome::Rejected(Reject::InsufficientFunds)
+    };
+
+    journal::record(&fee_tranx, outcome);
+
+    outcome
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::{apply, read, Fee, FeeKind};
+    use crate::ac::account;
+    use crate::tx::currency::Currency;
+    use crate::tx::id::{ClientId, TxId};
+    use crate::tx::transaction::Transaction;
+
+    #[test]
+    fn test_fee_compute() {
+        let flat = Fee {
+            r#type: "withdrawal".to_string(),
+            kind: FeeKind::Flat,
+            amount: 0.5,
+        };
+        let percent = Fee {
+            r#type: "withdrawal".to_string(),
+            kind: FeeKind::Percent,
+            amount: 1.5,
+        };
+
+        assert!(
+            flat.compute(200.0) == 0.5,
+            "wrong flat fee; expected {}, got {}",
+            0.5,
+            flat.compute(200.0)
+        );
+
+        assert!(
+            percent.compute(200.0) == 3.0,
+            "wrong percentage fee; expected {}, got {}",
+            3.0,
+            percent.compute(200.0)
+        );
+    }
+
+    #[test]
+    fn test_fee_read() {
+        let fees = read("type,kind,amount\nwithdrawal,flat,0.5\ndispute,percent,100\n".as_bytes());
+        assert!(
+            fees.as_ref().is_ok_and(|fees| fees.len() == 2),
+            "valid fee schedule should load; got {:?}",
+            fees
+        );
+
+        for schedule in [
+            "type,kind,amount\nwithdrawal,flat,0.5\nwithdrawal,flat,-5\n",
+            "type,kind,amount\nchargeback,percent,150\n",
+            "type,kind,amount\ndeposit,flat,1\n",
+        ] {
+            let fees = read(schedule.as_bytes());
+            assert!(
+                fees.is_err(),
+                "fee schedule should be rejected; expected an error, got {:?}",
+                fees
+            );
+        }
+    }
+
+    #[test]
+    fn test_fee_apply() {
+        let fee = Fee {
+            r#type: "withdrawal".to_string(),
+            kind: FeeKind::Flat,
+            amount: 1.0,
+        };
+
+        account::process(&Transaction::new(
+            "deposit".to_string(),
+            ClientId(101),
+            TxId(1010),
+            10.0,
+        ));
+        let withdrawal = Transaction::new("withdrawal".to_string(), ClientId(101), TxId(1011), 8.5);
+        account::process(&withdrawal);
+
+        let outcome = apply(&fee, &withdrawal);
+        assert!(outcome.is_applied(), "fee not applied; got {:?}", outcome);
+
+        let outcome = apply(&fee, &withdrawal);
+        assert!(
+            !outcome.is_applied(),
+            "fee should not overdraw the account; got {:?}",
+            outcome
+        );
+
+        let acct = account::ACCOUNTS
+            .lock()
+            .unwrap()
+            .read((ClientId(101), Currency::default()), |acc| *acc.unwrap());
+
+        assert!(
+            acct.available == 0.5 && acct.total == 0.5,
+            "invalid funds after fee; expected {}, got {}",
+            0.5,
+            acct.available
+        );
+    }
+
+    #[test]
+    fn test_fee_disputed_transfer() {
+        let fee = Fee {
+            r#type: "dispute".to_string(),
+            kind: FeeKind::Flat,
+            amount: 1.0,
+        };
+
+        let mut transfer =
+            Transaction::new("transfer".to_string(), ClientId(102), TxId(1021), 10.0);
+        transfer.to_client = Some(ClientId(103));
+        let dispute = Transaction::new("dispute".to_string(), ClientId(102), TxId(1021), 4.0);
+
+        account::process(&Transaction::new(
+            "deposit".to_string(),
+            ClientId(102),
+            TxId(1020),
+            20.0,
+        ));
+        account::process(&transfer);
+        account::process(&dispute);
+
+        let outcome = apply(&fee, &dispute);
+        assert!(outcome.is_applied(), "fee not applied; got {:?}", outcome);
+
+        // the receiver's funds are held, so the receiver pays the fee
+        let sender = account::get_account(ClientId(102), Currency::default());
+        let receiver = account::get_account(ClientId(103), Currency::default());
+        assert!(
+            sender.available == 10.0 && receiver.available == 5.0 && receiver.held == 4.0,
+            "fee charged to the wrong client; expected {} available for the receiver, got {:?} and {:?}",
+            5.0,
+            sender,
+            receiver
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use super::outcome::Outcome;
+use crate::tx::currency::Currency;
+use crate::tx::id::ClientId;
+use crate::tx::transaction::Transaction;
+
+// rows seen and rows applied per client, for checks that look back in time
+static HISTORY: Lazy<Mutex<HashMap<ClientId, Activity>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// What one client has done so far.
+#[derive(Debug, Default)]
+pub struct Activity {
+    /// Every row of the client, applied or not.
+    pub rows: usize,
+    /// The rows that were applied, oldest first.
+    pub applied: Vec<Applied>,
+}
+
+/// One applied row of a client.
+#[derive(Debug, Clone)]
+pub struct Applied {
+    /// Position among the client's rows, starting at 1.
+    pub row: usize,
+    /// The row type.
+    pub r#type: String,
+    /// The row timestamp, or when it was processed if it had none.
+    pub at: DateTime<Utc>,
+    /// The currency the row moved funds in.
+    pub currency: Currency,
+    /// The amount on the row.
+    pub amount: f32,
+}
+
+/// How far back a check looks.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Window {
+    /// The client's last N rows, counting the row being checked.
+    Transactions(usize),
+    /// Rows within this long before the row being checked.
+    Duration(TimeDelta),
+}
+
+impl Window {
+    /// A plain number counts rows, a number with s, m, h or d is a time window.
+    pub fn parse(window: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid window: {}", window);
+
+        let (count, unit) = match window.find(|c: char| !c.is_ascii_digit()) {
+            Some(at) => window.split_at(at),
+            None => (window, ""),
+        };
+        let count: i64 = count.parse().map_err(|_| invalid())?;
+
+        if count <= 0 {
+            return Err(invalid());
+        }
+
+        match unit {
+            "" => Ok(Window::Transactions(count as usize)),
+            "s" => Ok(Window::Duration(TimeDelta::seconds(count))),
+            "m" => Ok(Window::Duration(TimeDelta::minutes(count))),
+            "h" => Ok(Window::Duration(TimeDelta::hours(count))),
+            "d" => Ok(Window::Duration(TimeDelta::days(count))),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl Activity {
+    /// Applied rows of a type inside the window that ends at `tranx`.
+    pub fn recent<'a>(
+        &'a self,
+        r#type: &'a str,
+        window: Window,
+        tranx: &Transaction,
+    ) -> impl Iterator<Item = &'a Applied> {
+        let at = time_of(tranx);
+        // the row being checked is the client's next one
+        let row = self.rows + 1;
+
+        self.applied
+            .iter()
+            .filter(move |prior| prior.r#type == r#type)
+            .filter(move |prior| match window {
+                Window::Transactions(rows) => prior.row + rows > row,
+                Window::Duration(span) => prior.at > at - span && prior.at <= at,
+            })
+    }
+}
+
+/// Counts every row of a client, and keeps the ones that were applied.
+pub fn record(tranx: &Transaction, outcome: Outcome) {
+    let mut history = HISTORY.lock().unwrap();
+    let activity = history.entry(tranx.client).or_default();
+
+    activity.rows += 1;
+
+    if outcome.is_applied() {
+        activity.applied.push(Applied {
+            row: activity.rows,
+            r#type: tranx.r#type.to_string(),
+            at: time_of(tranx),
+            currency: tranx.currency,
+            amount: tranx.amount,
+        });
+    }
+}
+
+/// Runs `f` on the activity of a client, empty if it has none yet.
+pub fn read<F, R>(client: ClientId, f: F) -> R
+where
+    F: FnOnce(&Activity) -> R,
+{
+    let history = HISTORY.lock().unwrap();
+
+    match history.get(&client) {
+        Some(activity) => f(activity),
+        None => f(&Activity::default()),
+    }
+}
+
+/// Rows without a timestamp count as happening when they are processed.
+pub fn time_of(tranx: &Transaction) -> DateTime<Utc> {
+    tranx.timestamp.unwrap_or_else(Utc::now)
+}
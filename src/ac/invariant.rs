@@ -0,0 +1,418 @@
+use once_cell::sync::Lazy;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::sync::Mutex;
+
+use super::account::{self, Account, AccountKey, ACCOUNTS};
+use super::authorization;
+use super::ledger::{self, Book};
+use crate::tx::id::{ClientId, TxId};
+use crate::tx::journal::{self, Entry};
+use crate::tx::transaction::{self, Dispute, Transaction};
+
+// balances are kept to four decimal places, anything beyond one unit is drift
+const TOLERANCE: f64 = 0.0001;
+
+/// The invariant an account broke.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Check {
+    /// Total must equal available plus held.
+    Total,
+    /// Held can never go below zero.
+    NegativeHeld,
+    /// Total must match the sum of applied transactions.
+    AppliedTotal,
+    /// Held must match the amounts still under dispute or authorized.
+    DisputedHeld,
+    /// Available and held must match the balances of the client's ledger books.
+    Posted,
+}
+
+/// An account whose balances break one of the invariants.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Violation {
+    /// The invariant that does not hold.
+    pub check: Check,
+    /// The account as it was found.
+    pub account: Account,
+    /// The value the invariant expects.
+    pub expected: f64,
+    /// The value the account holds.
+    pub actual: f64,
+    /// The transaction after which the violation was found, when checking per row.
+    pub tx: Option<TxId>,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let check = match self.check {
+            Check::Total => "total does not equal available + held",
+            Check::NegativeHeld => "held is negative",
+            Check::AppliedTotal => "total does not match applied transactions",
+            Check::DisputedHeld => "held does not match open disputes and authorizations",
+            Check::Posted => "balance does not match ledger postings",
+        };
+
+        write!(f, "invariant violated")?;
+        if let Some(tx) = self.tx {
+            write!(f, " after tx {}", tx)?;
+        }
+
+        write!(
+            f,
+            ": {} for client {} ({}); expected {}, got {} (available {}, held {}, total {}, locked {}, closed {})",
+            check,
+            self.account.client,
+            self.account.currency,
+            self.expected,
+            self.actual,
+            self.account.available,
+            self.account.held,
+            self.account.total,
+            self.account.locked,
+            self.account.closed
+        )
+    }
+}
+
+// Recomputed balances of one bucket
+#[derive(Debug, Default, Copy, Clone)]
+struct Expected {
+    total: f64,
+    held: f64,
+}
+
+/// Checks every account held by the engine.
+pub fn check_all() -> Result<usize, Violation> {
+    let keys = account::CLIENTS.lock().unwrap().to_vec();
+    check(&keys)?;
+
+    Ok(keys.len())
+}
+
+/// Checks the accounts touched by one transaction.
+pub fn check_after(tranx: &Transaction) -> Result<(), Violation> {
+    let mut keys = account::client_keys(tranx.client);
+    if let Some(to_client) = tranx.to_client {
+        keys.extend(account::client_keys(to_client));
+    }
+
+    // disputes act on the stored transaction, which may belong to the transfer recipient
+    let stored: Option<Transaction> = transaction::TRANSACTIONS
+        .lock()
+        .unwrap()
+        .read(tranx.tx, |trx| trx.cloned());
+    if let Some(stored) = stored {
+        keys.extend(account::client_keys(stored.client));
+        if let Some(to_client) = stored.to_client {
+            keys.extend(account::client_keys(to_client));
+        }
+    }
+
+    check(&keys).map_err(|violation| Violation {
+        tx: Some(tranx.tx),
+        ..violation
+    })
+}
+
+fn check(keys: &[AccountKey]) -> Result<(), Violation> {
+    let clients: Vec<ClientId> = keys.iter().map(|key| key.0).collect();
+    let expected = recompute(&clients);
+
+    for key in keys {
+        let acct: Account = ACCOUNTS.lock().unwrap().read(*key, |acct| *acct.unwrap());
+        let exp = expected.get(key).copied().unwrap_or_default();
+
+        verify(&acct, exp)?;
+        verify_posted(&acct)?;
+    }
+
+    Ok(())
+}
+
+fn verify(acct: &Account, expected: Expected) -> Result<(), Violation> {
+    let violation = |check, expected, actual| Violation {
+        check,
+        account: *acct,
+        expected,
+        actual,
+        tx: None,
+    };
+
+    let available = acct.available as f64;
+    let held = acct.held as f64;
+    let total = acct.total as f64;
+
+    if (total - (available + held)).abs() > TOLERANCE {
+        return Err(violation(Check::Total, available + held, total));
+    }
+
+    if held < 0.0 {
+        return Err(violation(Check::NegativeHeld, 0.0, held));
+    }
+
+    if (total - expected.total).abs() > TOLERANCE {
+        return Err(violation(Check::AppliedTotal, expected.total, total));
+    }
+
+    if (held - expected.held).abs() > TOLERANCE {
+        return Err(violation(Check::DisputedHeld, expected.held, held));
+    }
+
+    Ok(())
+}
+
+fn verify_posted(acct: &Account) -> Result<(), Violation> {
+    let books = [
+        (Book::Available(acct.client), acct.available),
+        (Book::Held(acct.client), acct.held),
+    ];
+
+    for (book, actual) in books {
+        let posted = ledger::balance(book, acct.currency);
+
+        if (actual as f64 - posted).abs() > TOLERANCE {
+            return Err(Violation {
+                check: Check::Posted,
+                account: *acct,
+                expected: posted,
+                actual: actual as f64,
+                tx: None,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+// Applied totals per bucket, and the disputes and authorizations that concern
+// each client, built up from the journal as it grows so that a check does not
+// replay it from the start
+#[derive(Debug, Default)]
+struct Replay {
+    // journal entries already folded in
+    seen: usize,
+    totals: HashMap<AccountKey, f64>,
+    disputes: HashMap<ClientId, BTreeSet<TxId>>,
+    authorizations: HashMap<ClientId, BTreeSet<TxId>>,
+}
+
+static REPLAY: Lazy<Mutex<Replay>> = Lazy::new(|| Mutex::new(Replay::default()));
+
+impl Replay {
+    fn catch_up(&mut self) {
+        let entries: Vec<Entry> = journal::JOURNAL.lock().unwrap()[self.seen..].to_vec();
+        self.seen += entries.len();
+
+        for entry in entries.iter().filter(|entry| entry.applied) {
+            let amount = entry.amount as f64;
+
+            match entry.r#type.as_str() {
+                "deposit" => self.credit((entry.client, entry.currency), amount),
+                "withdrawal" | "fee" => self.credit((entry.client, entry.currency), -amount),
+                "transfer" => {
+                    self.credit((entry.client, entry.currency), -amount);
+                    if let Some(to_client) = entry.to_client {
+                        self.credit((to_client, entry.currency), amount);
+                    }
+                }
+                "convert" => {
+                    self.credit((entry.client, entry.currency), -amount);
+                    if let (Some(to_currency), Some(converted)) =
+                        (entry.to_currency, entry.converted)
+                    {
+                        self.credit((entry.client, to_currency), converted as f64);
+                    }
+                }
+                "dispute" => self.index_dispute(entry.tx),
+                "authorize" => {
+                    self.authorizations
+                        .entry(entry.client)
+                        .or_default()
+                        .insert(entry.tx);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn credit(&mut self, key: AccountKey, amount: f64) {
+        *self.totals.entry(key).or_default() += amount;
+    }
+
+    // a dispute concerns the client holding the funds, and the sender of a
+    // disputed transfer, who gets them back on a chargeback
+    fn index_dispute(&mut self, tx: TxId) {
+        let dispute: Option<Dispute> = transaction::DISPUTES
+            .lock()
+            .unwrap()
+            .read(tx, |dis| dis.copied());
+        let stored: Option<Transaction> = transaction::TRANSACTIONS
+            .lock()
+            .unwrap()
+            .read(tx, |trx| trx.cloned());
+
+        if let (Some(dispute), Some(stored)) = (dispute, stored) {
+            for client in [dispute.client, stored.client] {
+                self.disputes.entry(client).or_default().insert(tx);
+            }
+        }
+    }
+}
+
+// Rebuilds the balances of the given clients from the applied journal entries,
+// the dispute store and the authorization store
+fn recompute(clients: &[ClientId]) -> HashMap<AccountKey, Expected> {
+    let mut replay = REPLAY.lock().unwrap();
+    replay.catch_up();
+
+    let clients: HashSet<ClientId> = clients.iter().copied().collect();
+    let mut expected: HashMap<AccountKey, Expected> = HashMap::new();
+    let credit = |expected: &mut HashMap<AccountKey, Expected>, key: AccountKey, amount: f64| {
+        if clients.contains(&key.0) {
+            expected.entry(key).or_default().total += amount;
+        }
+    };
+
+    let mut disputed: BTreeSet<TxId> = BTreeSet::new();
+    let mut authorized: BTreeSet<TxId> = BTreeSet::new();
+
+    for client in clients.iter() {
+        for key in account::client_keys(*client) {
+            let total = replay.totals.get(&key).copied().unwrap_or_default();
+            credit(&mut expected, key, total);
+        }
+
+        disputed.extend(replay.disputes.get(client).into_iter().flatten());
+        authorized.extend(replay.authorizations.get(client).into_iter().flatten());
+    }
+
+    for tx in disputed {
+        let dispute: Dispute = match transaction::DISPUTES
+            .lock()
+            .unwrap()
+            .read(tx, |dis| dis.copied())
+        {
+            Some(dispute) => dispute,
+            None => continue,
+        };
+        let stored: Transaction = match transaction::TRANSACTIONS
+            .lock()
+            .unwrap()
+            .read(tx, |trx| trx.cloned())
+        {
+            Some(stored) => stored,
+            None => continue,
+        };
+
+        let charged_back = dispute.charged_back as f64;
+        credit(
+            &mut expected,
+            (dispute.client, stored.currency),
+            -charged_back,
+        );
+
+        // a charged back transfer goes back to the sender
+        if stored.r#type == "transfer" {
+            credit(
+                &mut expected,
+                (stored.client, stored.currency),
+                charged_back,
+            );
+        }
+
+        if clients.contains(&dispute.client) {
+            expected
+                .entry((dispute.client, stored.currency))
+                .or_default()
+                .held += dispute.held as f64;
+        }
+    }
+
+    // captures take funds out, open authorizations keep them held
+    for auth in authorized.into_iter().filter_map(authorization::find) {
+        let key = (auth.client, auth.currency);
+        credit(&mut expected, key, -(auth.captured as f64));
+
+        if clients.contains(&auth.client) {
+            expected.entry(key).or_default().held += auth.held as f64;
+        }
+    }
+
+    expected
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::{check_after, verify, Check, Expected};
+    use crate::ac::account::{self, Account};
+    use crate::tx::id::{ClientId, TxId};
+    use crate::tx::transaction::Transaction;
+
+    #[test]
+    fn test_invariants_hold() {
+        let mut transfer = Transaction::new("transfer".to_string(), ClientId(120), TxId(1202), 4.0);
+        transfer.to_client = Some(ClientId(121));
+
+        let rows = vec![
+            Transaction::new("deposit".to_string(), ClientId(120), TxId(1200), 10.0),
+            Transaction::new("withdrawal".to_string(), ClientId(120), TxId(1201), 2.5),
+            // rejected, must not count towards the total
+            Transaction::new("withdrawal".to_string(), ClientId(120), TxId(1203), 50.0),
+            transfer,
+            Transaction::new("dispute".to_string(), ClientId(121), TxId(1202), 1.0),
+            Transaction::new("chargeback".to_string(), ClientId(121), TxId(1202), 0.0),
+            Transaction::new("dispute".to_string(), ClientId(120), TxId(1200), 2.0),
+        ];
+
+        for tranx in rows {
+            account::process(&tranx);
+            let result = check_after(&tranx);
+
+            assert!(
+                result.is_ok(),
+                "invariants should hold after tx {}; got {}",
+                tranx.tx,
+                result.unwrap_err()
+            );
+        }
+    }
+
+    #[test]
+    fn test_invariant_violation() {
+        let mut acct = Account::new(ClientId(122), 5.0, 1.0);
+        acct.total = 7.0;
+
+        let expected = Expected {
+            total: 6.0,
+            held: 1.0,
+        };
+
+        let violation = verify(&acct, expected).unwrap_err();
+        assert!(
+            violation.check == Check::Total,
+            "wrong violation; expected {:?}, got {:?}",
+            Check::Total,
+            violation.check
+        );
+
+        acct.total = 6.0;
+        let violation = verify(
+            &acct,
+            Expected {
+                held: 0.5,
+                ..expected
+            },
+        )
+        .unwrap_err();
+        assert!(
+            violation.check == Check::DisputedHeld,
+            "wrong violation; expected {:?}, got {:?}",
+            Check::DisputedHeld,
+            violation.check
+        );
+
+        assert!(verify(&acct, expected).is_ok(), "balances should verify");
+    }
+}
@@ -0,0 +1,275 @@
+use once_cell::sync::Lazy;
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::fmt;
+use std::sync::Mutex;
+
+use crate::error::Error;
+use crate::tx::currency::Currency;
+use crate::tx::id::{ClientId, TxId};
+
+/// Every movement of funds, in the order it was posted.
+pub static POSTINGS: Lazy<Mutex<Vec<Posting>>> = Lazy::new(|| Mutex::new(vec![]));
+
+// credits less debits of every book, kept up to date as postings are made
+static BALANCES: Lazy<Mutex<HashMap<(Book, Currency), f64>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+// a trial balance that is off by less than this is rounding noise
+const TOLERANCE: f64 = 0.0001;
+
+/// A book that postings debit and credit. Client books hold what the client is
+/// owed, system books are the other side of money entering or leaving.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Book {
+    /// Funds the client can spend.
+    Available(ClientId),
+    /// Funds of the client under dispute or authorized.
+    Held(ClientId),
+    /// Funds deposited from or withdrawn to the outside world.
+    External,
+    /// Fees charged to clients.
+    Fees,
+    /// Funds taken back by chargebacks.
+    Chargebacks,
+    /// Counterpart of both legs of a currency conversion.
+    Exchange,
+}
+
+impl fmt::Display for Book {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Book::Available(client) => write!(f, "available:{}", client),
+            Book::Held(client) => write!(f, "held:{}", client),
+            Book::External => f.write_str("external"),
+            Book::Fees => f.write_str("fees"),
+            Book::Chargebacks => f.write_str("chargebacks"),
+            Book::Exchange => f.write_str("exchange"),
+        }
+    }
+}
+
+impl Serialize for Book {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+/// A balanced entry moving `amount` from the debited book to the credited book.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub struct Posting {
+    /// Position in the ledger, starting at 1.
+    pub seq: usize,
+    /// The tx id of the row that made the posting.
+    pub tx: TxId,
+    /// The currency of the amount.
+    pub currency: Currency,
+    /// The book the amount leaves.
+    pub debit: Book,
+    /// The book the amount reaches.
+    pub credit: Book,
+    /// The amount moved, always positive.
+    pub amount: f32,
+}
+
+/// One line of the trial balance.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub struct Line {
+    /// The book the line is about.
+    pub book: Book,
+    /// The currency of the totals.
+    pub currency: Currency,
+    /// Sum of the amounts debited from the book.
+    pub debits: f64,
+    /// Sum of the amounts credited to the book.
+    pub credits: f64,
+    /// Credits less debits.
+    pub balance: f64,
+}
+
+/// Records a posting and updates the running balances of both books.
+pub fn post(tx: TxId, currency: Currency, debit: Book, credit: Book, amount: f32) -> Posting {
+    let mut postings = POSTINGS.lock().unwrap();
+    let posting = Posting {
+        seq: postings.len() + 1,
+        tx,
+        currency,
+        debit,
+        credit,
+        amount,
+    };
+    postings.push(posting);
+
+    let mut balances = BALANCES.lock().unwrap();
+    *balances.entry((debit, currency)).or_default() -= amount as f64;
+    *balances.entry((credit, currency)).or_default() += amount as f64;
+
+    posting
+}
+
+/// How many postings have been made.
+pub fn posted() -> usize {
+    POSTINGS.lock().unwrap().len()
+}
+
+/// Postings made after the first `count`.
+pub fn since(count: usize) -> Vec<Posting> {
+    POSTINGS.lock().unwrap()[count..].to_vec()
+}
+
+/// Credits less debits of one book.
+pub fn balance(book: Book, currency: Currency) -> f64 {
+    BALANCES
+        .lock()
+        .unwrap()
+        .get(&(book, currency))
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Debits, credits and balance of every book, per currency.
+pub fn trial_balance() -> Vec<Line> {
+    let mut lines: BTreeMap<(Currency, Book), Line> = BTreeMap::new();
+
+    for posting in POSTINGS.lock().unwrap().iter() {
+        let amount = posting.amount as f64;
+
+        let debit = lines
+            .entry((posting.currency, posting.debit))
+            .or_insert_with(|| Line::new(posting.debit, posting.currency));
+        debit.debits += amount;
+        debit.balance -= amount;
+
+        let credit = lines
+            .entry((posting.currency, posting.credit))
+            .or_insert_with(|| Line::new(posting.credit, posting.currency));
+        credit.credits += amount;
+        credit.balance += amount;
+    }
+
+    lines.into_values().collect()
+}
+
+/// Writes the trial balance and fails if any currency does not net to zero.
+pub fn export_trial_balance(path: &OsString) -> Result<(), Error> {
+    let lines = trial_balance();
+
+    let mut csv_writer = csv::Writer::from_path(path)?;
+    for line in lines.iter() {
+        csv_writer.serialize(line)?;
+    }
+    csv_writer.flush()?;
+
+    let mut nets: BTreeMap<Currency, f64> = BTreeMap::new();
+    for line in lines {
+        *nets.entry(line.currency).or_default() += line.balance;
+    }
+
+    for (currency, net) in nets {
+        if net.abs() > TOLERANCE {
+            return Err(Error::Invalid(format!(
+                "trial balance for {} does not net to zero: {}",
+                currency, net
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+impl Line {
+    fn new(book: Book, currency: Currency) -> Self {
+        Self {
+            book,
+            currency,
+            debits: 0.0,
+            credits: 0.0,
+            balance: 0.0,
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::{balance, post, trial_balance, Book};
+    use crate::ac::account;
+    use crate::tx::currency::Currency;
+    use crate::tx::id::{ClientId, TxId};
+    use crate::tx::transaction::Transaction;
+
+    #[test]
+    fn test_ledger_postings() {
+        let client = ClientId(130);
+        let eur = Currency::new("LEDG").unwrap();
+
+        let mut deposit = Transaction::new("deposit".to_string(), client, TxId(1300), 10.0);
+        deposit.currency = eur;
+        let mut withdrawal = Transaction::new("withdrawal".to_string(), client, TxId(1301), 3.0);
+        withdrawal.currency = eur;
+        let mut dispute = Transaction::new("dispute".to_string(), client, TxId(1300), 2.0);
+        dispute.currency = eur;
+
+        account::process(&deposit);
+        account::process(&withdrawal);
+        account::process(&dispute);
+
+        let acct = account::get_account(client, eur);
+        let available = balance(Book::Available(client), eur);
+        let held = balance(Book::Held(client), eur);
+
+        assert!(
+            acct.available as f64 == available && acct.held as f64 == held,
+            "account not derived from postings; expected {}/{}, got {}/{}",
+            available,
+            held,
+            acct.available,
+            acct.held
+        );
+
+        assert!(
+            balance(Book::External, eur) == -7.0,
+            "wrong external balance; expected {}, got {}",
+            -7.0,
+            balance(Book::External, eur)
+        );
+    }
+
+    #[test]
+    fn test_trial_balance_nets_to_zero() {
+        let currency = Currency::new("TRIAL").unwrap();
+        post(
+            TxId(1310),
+            currency,
+            Book::External,
+            Book::Available(ClientId(131)),
+            5.0,
+        );
+        post(
+            TxId(1311),
+            currency,
+            Book::Available(ClientId(131)),
+            Book::Held(ClientId(131)),
+            2.0,
+        );
+        post(
+            TxId(1311),
+            currency,
+            Book::Held(ClientId(131)),
+            Book::Chargebacks,
+            2.0,
+        );
+
+        let net: f64 = trial_balance()
+            .iter()
+            .filter(|line| line.currency == currency)
+            .map(|line| line.balance)
+            .sum();
+
+        assert!(net == 0.0, "trial balance should net to zero; got {}", net);
+    }
+}
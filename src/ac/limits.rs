@@ -0,0 +1,289 @@
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::ffi::OsString;
+use std::sync::Mutex;
+
+use super::account::round_up;
+use super::history::{self, Window};
+use super::outcome::Reject;
+use crate::error::Error;
+use crate::tx::transaction::Transaction;
+
+/// The loaded withdrawal limits; none by default.
+pub static LIMITS: Lazy<Mutex<Vec<Limit>>> = Lazy::new(|| Mutex::new(vec![]));
+
+// rows that spend funds and count toward the limits. A capture settles an
+// authorization that was already counted, so it is not counted again.
+const SPENDING: [&str; 2] = ["withdrawal", "authorize"];
+
+/// A compliance limit on withdrawals and authorizations.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Limit {
+    /// Largest amount a single withdrawal or authorization may take.
+    Single(f32),
+    /// Most withdrawals and authorizations a client may make within the window.
+    Count {
+        /// Rows allowed within the window.
+        max: usize,
+        /// How far back rows are counted.
+        window: Window,
+    },
+    /// Largest total a client may withdraw or authorize per UTC day and currency.
+    Daily(f32),
+}
+
+// One line of the limits file, e.g. `count,3,1h`
+#[derive(Debug, Deserialize)]
+struct Row {
+    limit: String,
+    value: f32,
+    #[serde(default)]
+    window: Option<String>,
+}
+
+/// Loads a limits CSV with `limit,value,window` columns.
+pub fn load(path: &OsString) -> Result<(), Error> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(path)?;
+
+    let mut limits = LIMITS.lock().unwrap();
+    for result in rdr.deserialize() {
+        let row: Row = result?;
+        limits.push(parse(&row.limit, row.value, row.window.as_deref()).map_err(Error::Invalid)?);
+    }
+
+    Ok(())
+}
+
+/// Reads one limit, e.g. `count` with `3` and `1h`.
+pub fn parse(limit: &str, value: f32, window: Option<&str>) -> Result<Limit, String> {
+    if value <= 0.0 {
+        return Err(format!("invalid {} limit: {}", limit, value));
+    }
+
+    match (limit, window) {
+        ("single", None) => Ok(Limit::Single(value)),
+        ("daily", None) => Ok(Limit::Daily(value)),
+        ("count", Some(window)) if value.fract() == 0.0 => Ok(Limit::Count {
+            max: value as usize,
+            window: Window::parse(window)?,
+        }),
+        ("count", _) => Err(format!(
+            "count limit needs a whole number and a window: {}",
+            value
+        )),
+        (other, _) => Err(format!("unknown limit: {}", other)),
+    }
+}
+
+/// Checks a withdrawal or authorization against the loaded limits.
+pub fn check(tranx: &Transaction) -> Option<Reject> {
+    evaluate(&LIMITS.lock().unwrap(), tranx)
+}
+
+/// The first of `limits` that `tranx` would break.
+pub fn evaluate(limits: &[Limit], tranx: &Transaction) -> Option<Reject> {
+    let at = history::time_of(tranx);
+
+    history::read(tranx.client, |activity| {
+        limits.iter().find_map(|limit| match *limit {
+            Limit::Single(max) if tranx.amount > max => Some(Reject::WithdrawalTooLarge),
+            Limit::Count { max, window } => {
+                let recent: usize = SPENDING
+                    .iter()
+                    .map(|r#type| activity.recent(r#type, window, tranx).count())
+                    .sum();
+
+                (recent + 1 > max).then_some(Reject::TooManyWithdrawals)
+            }
+            Limit::Daily(max) => {
+                let withdrawn: f32 = activity
+                    .applied
+                    .iter()
+                    .filter(|prior| SPENDING.contains(&prior.r#type.as_str()))
+                    .filter(|prior| prior.currency == tranx.currency)
+                    .filter(|prior| prior.at.date_naive() == at.date_naive())
+                    .map(|prior| prior.amount)
+                    .sum();
+
+                (round_up(withdrawn + tranx.amount) > max).then_some(Reject::DailyLimitExceeded)
+            }
+            _ => None,
+        })
+    })
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::{evaluate, parse, Limit};
+    use crate::ac::history::{record, Window};
+    use crate::ac::outcome::{Outcome, Reject};
+    use crate::tx::id::{ClientId, TxId};
+    use crate::tx::timestamp;
+    use crate::tx::transaction::Transaction;
+    use chrono::TimeDelta;
+
+    fn withdrawal(client: u64, tx: u64, amount: f32, at: &str) -> Transaction {
+        spend("withdrawal", client, tx, amount, at)
+    }
+
+    fn spend(r#type: &str, client: u64, tx: u64, amount: f32, at: &str) -> Transaction {
+        let mut tranx = Transaction::new(r#type.to_string(), ClientId(client), TxId(tx), amount);
+        tranx.timestamp = Some(timestamp::parse(at).unwrap());
+
+        tranx
+    }
+
+    #[test]
+    fn test_limits_parse() {
+        let hourly = parse("count", 3.0, Some("1h")).unwrap();
+        let rows = parse("count", 2.0, Some("10")).unwrap();
+
+        assert!(
+            hourly
+                == Limit::Count {
+                    max: 3,
+                    window: Window::Duration(TimeDelta::hours(1))
+                }
+                && rows
+                    == Limit::Count {
+                        max: 2,
+                        window: Window::Transactions(10)
+                    },
+            "wrong count limits; got {:?} and {:?}",
+            hourly,
+            rows
+        );
+
+        assert!(
+            parse("count", 3.0, None).is_err() && parse("weekly", 1.0, None).is_err(),
+            "count without a window and unknown limits should fail"
+        );
+    }
+
+    #[test]
+    fn test_limits_single_and_daily() {
+        let limits = [Limit::Single(50.0), Limit::Daily(80.0)];
+
+        let large = withdrawal(180, 1800, 60.0, "2024-01-01T09:00:00Z");
+        let reject = evaluate(&limits, &large);
+        assert!(
+            reject == Some(Reject::WithdrawalTooLarge),
+            "wrong reject; expected {:?}, got {:?}",
+            Reject::WithdrawalTooLarge,
+            reject
+        );
+
+        record(
+            &withdrawal(180, 1801, 50.0, "2024-01-01T10:00:00Z"),
+            Outcome::Applied,
+        );
+
+        let same_day = withdrawal(180, 1802, 40.0, "2024-01-01T23:00:00Z");
+        let reject = evaluate(&limits, &same_day);
+        assert!(
+            reject == Some(Reject::DailyLimitExceeded),
+            "wrong reject; expected {:?}, got {:?}",
+            Reject::DailyLimitExceeded,
+            reject
+        );
+
+        let next_day = withdrawal(180, 1803, 40.0, "2024-01-02T01:00:00Z");
+        assert!(
+            evaluate(&limits, &next_day).is_none(),
+            "daily total should start over the next day"
+        );
+    }
+
+    #[test]
+    fn test_limits_count() {
+        let hourly = [Limit::Count {
+            max: 2,
+            window: Window::Duration(TimeDelta::hours(1)),
+        }];
+        let per_rows = [Limit::Count {
+            max: 2,
+            window: Window::Transactions(4),
+        }];
+
+        record(
+            &withdrawal(181, 1810, 1.0, "2024-01-01T10:00:00Z"),
+            Outcome::Applied,
+        );
+        record(
+            &withdrawal(181, 1811, 1.0, "2024-01-01T10:30:00Z"),
+            Outcome::Applied,
+        );
+
+        let third = withdrawal(181, 1812, 1.0, "2024-01-01T10:45:00Z");
+        assert!(
+            evaluate(&hourly, &third) == Some(Reject::TooManyWithdrawals)
+                && evaluate(&per_rows, &third) == Some(Reject::TooManyWithdrawals),
+            "third withdrawal should exceed both windows"
+        );
+
+        let later = withdrawal(181, 1813, 1.0, "2024-01-01T11:15:00Z");
+        assert!(
+            evaluate(&hourly, &later).is_none(),
+            "first withdrawal should have left the hour window"
+        );
+
+        // rejected rows still count toward the row window, but not as withdrawals
+        record(&third, Outcome::Rejected(Reject::TooManyWithdrawals));
+        record(&third, Outcome::Rejected(Reject::TooManyWithdrawals));
+        assert!(
+            evaluate(&per_rows, &later).is_none(),
+            "first withdrawal should have left the row window"
+        );
+    }
+
+    #[test]
+    fn test_limits_authorizations() {
+        let daily = [Limit::Daily(100.0)];
+        let hourly = [Limit::Count {
+            max: 2,
+            window: Window::Duration(TimeDelta::hours(1)),
+        }];
+
+        // an authorization and its capture spend 90 once
+        record(
+            &spend("authorize", 182, 1820, 90.0, "2024-01-01T10:00:00Z"),
+            Outcome::Applied,
+        );
+        record(
+            &spend("capture", 182, 1820, 90.0, "2024-01-01T10:05:00Z"),
+            Outcome::Applied,
+        );
+
+        for r#type in ["authorize", "withdrawal"] {
+            let again = spend(r#type, 182, 1821, 90.0, "2024-01-01T10:10:00Z");
+            let reject = evaluate(&daily, &again);
+            assert!(
+                reject == Some(Reject::DailyLimitExceeded),
+                "wrong {} reject; expected {:?}, got {:?}",
+                r#type,
+                Reject::DailyLimitExceeded,
+                reject
+            );
+        }
+
+        let small = spend("withdrawal", 182, 1822, 10.0, "2024-01-01T10:15:00Z");
+        assert!(
+            evaluate(&daily, &small).is_none() && evaluate(&hourly, &small).is_none(),
+            "the capture should not count a second time"
+        );
+
+        record(&small, Outcome::Applied);
+
+        let third = spend("authorize", 182, 1823, 1.0, "2024-01-01T10:20:00Z");
+        let reject = evaluate(&hourly, &third);
+        assert!(
+            reject == Some(Reject::TooManyWithdrawals),
+            "wrong reject; expected {:?}, got {:?}",
+            Reject::TooManyWithdrawals,
+            reject
+        );
+    }
+}
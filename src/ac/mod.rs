@@ -1,4 +1,38 @@
+/// Balances per client and currency, and the engine that applies transactions to them.
 pub mod account;
+/// Card-style authorization holds.
+pub mod authorization;
 
+/// The open-disputes report.
+pub mod disputes;
+/// Account change events.
+pub mod events;
 mod export;
+/// The fee schedule.
+pub mod fee;
+/// Per-client activity for checks that look back in time.
+pub mod history;
+/// Checks that balances agree with the journal, disputes and ledger.
+pub mod invariant;
+/// The double-entry ledger underneath the accounts.
+pub mod ledger;
+/// Withdrawal limits.
+pub mod limits;
+/// What the engine did with a transaction.
+pub mod outcome;
+/// The queue of transactions held for manual review.
+pub mod review;
+/// Declarative risk rules.
+pub mod rules;
+/// Per-client statements.
+pub mod statement;
+/// Run summary statistics.
+pub mod summary;
+// the original tests predate these lints
+#[allow(
+    clippy::module_inception,
+    clippy::bool_comparison,
+    clippy::clone_on_copy,
+    clippy::partialeq_to_none
+)]
 mod tests;
@@ -0,0 +1,145 @@
+use serde::Serialize;
+
+/// What [`process`](crate::process) did with a transaction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The balances were changed.
+    Applied,
+    /// Nothing was changed, for the given reason.
+    Rejected(Reject),
+    /// Parked for review; nothing changes until it is approved.
+    Pending,
+}
+
+/// Why a transaction was rejected. Serialized in snake case, e.g.
+/// `insufficient_funds`, which is also what the journal and reports show.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reject {
+    /// The `type` is not one the engine knows.
+    UnsupportedType,
+    /// The amount is negative, or zero where a positive amount is needed.
+    InvalidAmount,
+    /// An exact replay of a transaction already on record.
+    DuplicateTx,
+    /// A different transaction reusing a tx id already on record.
+    ConflictingTx,
+    /// The referenced transaction was never applied.
+    UnknownTx,
+    /// An admin row for a client without an account.
+    UnknownClient,
+    /// A transfer without a receiving client, or to the sender.
+    InvalidCounterparty,
+    /// A conversion without a target currency, or into the same one.
+    InvalidCurrency,
+    /// No rate is loaded for the currency pair.
+    UnknownRate,
+    /// Rounding would credit more than the debited amount is worth.
+    ValueCreated,
+    /// The account is locked by a chargeback or a freeze.
+    AccountLocked,
+    /// The account is closed.
+    AccountClosed,
+    /// An unlock for an account that is not locked.
+    AccountNotLocked,
+    /// Available funds do not cover the amount.
+    InsufficientFunds,
+    /// Above the `single` withdrawal limit.
+    WithdrawalTooLarge,
+    /// Above the `count` withdrawal limit.
+    TooManyWithdrawals,
+    /// Above the `daily` withdrawal limit.
+    DailyLimitExceeded,
+    /// A close while funds are still held.
+    FundsHeld,
+    /// The whole transaction is already under dispute.
+    DisputeExists,
+    /// Conversions and authorizations cannot be disputed.
+    NotDisputable,
+    /// More than what is left undisputed.
+    ExceedsUndisputed,
+    /// More than the dispute still holds.
+    ExceedsDisputed,
+    /// A resolve or chargeback without a dispute.
+    UnknownDispute,
+    /// The dispute was already settled.
+    DisputeResolved,
+    /// A risk rule rejected the transaction.
+    RuleRejected,
+    /// No transaction of the client is parked under the tx id.
+    UnknownReview,
+    /// The parked transaction was already decided.
+    ReviewClosed,
+    /// No authorization of the client under the tx id.
+    UnknownAuthorization,
+    /// The authorization was captured, voided or expired.
+    AuthorizationClosed,
+    /// More than the authorization still holds.
+    ExceedsAuthorized,
+}
+
+impl Outcome {
+    /// Whether the balances were changed.
+    pub fn is_applied(&self) -> bool {
+        *self == Outcome::Applied
+    }
+
+    /// Whether the transaction was parked for review.
+    pub fn is_pending(&self) -> bool {
+        *self == Outcome::Pending
+    }
+
+    /// Why the transaction was rejected, if it was.
+    pub fn reason(&self) -> Option<Reject> {
+        match self {
+            Outcome::Applied | Outcome::Pending => None,
+            Outcome::Rejected(reason) => Some(*reason),
+        }
+    }
+}
+
+impl Reject {
+    /// Same names as the serialized form, for plain text output.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Reject::UnsupportedType => "unsupported_type",
+            Reject::InvalidAmount => "invalid_amount",
+            Reject::DuplicateTx => "duplicate_tx",
+            Reject::ConflictingTx => "conflicting_tx",
+            Reject::UnknownTx => "unknown_tx",
+            Reject::UnknownClient => "unknown_client",
+            Reject::InvalidCounterparty => "invalid_counterparty",
+            Reject::InvalidCurrency => "invalid_currency",
+            Reject::UnknownRate => "unknown_rate",
+            Reject::ValueCreated => "value_created",
+            Reject::AccountLocked => "account_locked",
+            Reject::AccountClosed => "account_closed",
+            Reject::AccountNotLocked => "account_not_locked",
+            Reject::InsufficientFunds => "insufficient_funds",
+            Reject::WithdrawalTooLarge => "withdrawal_too_large",
+            Reject::TooManyWithdrawals => "too_many_withdrawals",
+            Reject::DailyLimitExceeded => "daily_limit_exceeded",
+            Reject::FundsHeld => "funds_held",
+            Reject::DisputeExists => "dispute_exists",
+            Reject::NotDisputable => "not_disputable",
+            Reject::ExceedsUndisputed => "exceeds_undisputed",
+            Reject::ExceedsDisputed => "exceeds_disputed",
+            Reject::UnknownDispute => "unknown_dispute",
+            Reject::DisputeResolved => "dispute_resolved",
+            Reject::RuleRejected => "rule_rejected",
+            Reject::UnknownReview => "unknown_review",
+            Reject::ReviewClosed => "review_closed",
+            Reject::UnknownAuthorization => "unknown_authorization",
+            Reject::AuthorizationClosed => "authorization_closed",
+            Reject::ExceedsAuthorized => "exceeds_authorized",
+        }
+    }
+
+    /// Whether a withdrawal limit was hit.
+    pub fn is_limit(&self) -> bool {
+        matches!(
+            self,
+            Reject::WithdrawalTooLarge | Reject::TooManyWithdrawals | Reject::DailyLimitExceeded
+        )
+    }
+}
@@ -0,0 +1,241 @@
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::ffi::OsString;
+use std::fs::File;
+use std::sync::Mutex;
+
+use super::outcome::{Outcome, Reject};
+use crate::error::Error;
+use crate::storage::{Storage, StoreKey};
+use crate::tx::id::TxId;
+use crate::tx::journal;
+use crate::tx::transaction::Transaction;
+
+/// Transactions parked by a `hold` rule, kept after a decision for the record.
+pub static REVIEW: Lazy<Mutex<Storage<TxId, Parked>>> = Lazy::new(|| Mutex::new(Storage::new()));
+
+/// Where a parked transaction stands.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// Waiting for a decision.
+    Pending,
+    /// Approved and applied.
+    Approved,
+    /// Declined without touching any balance.
+    Declined,
+}
+
+/// A transaction waiting in, or decided from, the review queue.
+#[derive(Debug, Clone, Serialize)]
+pub struct Parked {
+    /// The transaction as it was submitted.
+    pub tranx: Transaction,
+    /// The rule that held the transaction.
+    pub rule: String,
+    /// Transactions processed before it was parked.
+    pub parked: usize,
+    /// Pending until an `approve` or `decline` row decides it.
+    pub status: Status,
+}
+
+impl StoreKey for Parked {
+    type Key = TxId;
+
+    fn key(&self) -> Self::Key {
+        self.tranx.tx
+    }
+}
+
+/// Parks a transaction until it is approved or declined.
+pub fn park(tranx: &Transaction, rule: &str) -> Outcome {
+    let review = REVIEW.lock().unwrap();
+
+    let waiting = review.read(tranx.tx, |parked| {
+        parked.is_some_and(|parked| parked.status == Status::Pending)
+    });
+
+    if waiting {
+        return Outcome::Rejected(Reject::DuplicateTx);
+    }
+
+    review.insert(Parked {
+        tranx: tranx.clone(),
+        rule: rule.to_string(),
+        parked: journal::processed(),
+        status: Status::Pending,
+    });
+
+    Outcome::Pending
+}
+
+/// The pending transaction an `approve` or `decline` row refers to, left in the queue.
+pub fn find(tranx: &Transaction) -> Result<Transaction, Reject> {
+    REVIEW
+        .lock()
+        .unwrap()
+        .read(tranx.tx, |parked| match parked {
+            Some(parked) if parked.tranx.client == tranx.client => match parked.status {
+                Status::Pending => Ok(parked.tranx.clone()),
+                _ => Err(Reject::ReviewClosed),
+            },
+            _ => Err(Reject::UnknownReview),
+        })
+}
+
+/// Takes a pending transaction out of the queue for an `approve` or `decline` row.
+pub fn decide(tranx: &Transaction, status: Status) -> Result<Transaction, Reject> {
+    REVIEW
+        .lock()
+        .unwrap()
+        .modify(tranx.tx, |parked| match parked {
+            Some(parked) if parked.tranx.client == tranx.client => {
+                if parked.status != Status::Pending {
+                    return Err(Reject::ReviewClosed);
+                }
+
+                parked.status = status;
+                Ok(parked.tranx.clone())
+            }
+            _ => Err(Reject::UnknownReview),
+        })
+}
+
+/// Transactions still waiting for a decision, oldest first.
+pub fn pending() -> Vec<Parked> {
+    let mut pending: Vec<Parked> = REVIEW.lock().unwrap().reads(|review| {
+        review
+            .map(|(_, parked)| parked)
+            .filter(|parked| parked.status == Status::Pending)
+            .cloned()
+            .collect()
+    });
+    pending.sort_by_key(|parked| (parked.parked, parked.tranx.tx));
+
+    pending
+}
+
+/// Writes the pending transactions to `path` as JSON and returns how many there were.
+pub fn export_pending(path: &OsString) -> Result<usize, Error> {
+    let pending = pending();
+    serde_json::to_writer_pretty(File::create(path)?, &pending)?;
+
+    Ok(pending.len())
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::{decide, find, park, pending, Status};
+    use crate::ac::account;
+    use crate::ac::outcome::{Outcome, Reject};
+    use crate::tx::id::{ClientId, TxId};
+    use crate::tx::transaction::Transaction;
+
+    #[test]
+    fn test_review_park_and_decide() {
+        let deposit = Transaction::new("deposit".to_string(), ClientId(195), TxId(1950), 9000.0);
+        let approve = Transaction::new("approve".to_string(), ClientId(195), TxId(1950), 0.0);
+        let stranger = Transaction::new("approve".to_string(), ClientId(196), TxId(1950), 0.0);
+
+        let outcome = park(&deposit, "large deposit");
+        assert!(
+            outcome == Outcome::Pending,
+            "wrong outcome; expected {:?}, got {:?}",
+            Outcome::Pending,
+            outcome
+        );
+
+        assert!(
+            park(&deposit, "large deposit") == Outcome::Rejected(Reject::DuplicateTx),
+            "a transaction should only wait once"
+        );
+
+        assert!(
+            pending().iter().any(|parked| parked.tranx == deposit),
+            "parked transaction not pending"
+        );
+
+        let denied = decide(&stranger, Status::Approved);
+        assert!(
+            denied == Err(Reject::UnknownReview),
+            "another client may not release the transaction; got {:?}",
+            denied
+        );
+
+        let released = decide(&approve, Status::Approved);
+        assert!(
+            released == Ok(deposit.clone()),
+            "wrong released transaction; got {:?}",
+            released
+        );
+
+        let again = decide(&approve, Status::Declined);
+        assert!(
+            again == Err(Reject::ReviewClosed),
+            "a decided transaction cannot be decided again; got {:?}",
+            again
+        );
+    }
+
+    #[test]
+    fn test_review_rejected_approval() {
+        let client = ClientId(194);
+        let withdrawal = Transaction::new("withdrawal".to_string(), client, TxId(1941), 50.0);
+        let approve = Transaction::new("approve".to_string(), client, TxId(1941), 0.0);
+
+        account::process(&Transaction::new(
+            "deposit".to_string(),
+            client,
+            TxId(1940),
+            10.0,
+        ));
+        park(&withdrawal, "large withdrawal");
+
+        let outcome = account::process(&approve);
+        assert!(
+            outcome == Outcome::Rejected(Reject::InsufficientFunds),
+            "wrong outcome; expected {:?}, got {:?}",
+            Reject::InsufficientFunds,
+            outcome
+        );
+
+        // a rejected release leaves the row waiting for another decision
+        let waiting = find(&approve);
+        assert!(
+            waiting == Ok(withdrawal.clone()),
+            "withdrawal should still be pending; got {:?}",
+            waiting
+        );
+
+        account::process(&Transaction::new(
+            "deposit".to_string(),
+            client,
+            TxId(1942),
+            100.0,
+        ));
+
+        let outcome = account::process(&approve);
+        assert!(
+            outcome == Outcome::Applied,
+            "wrong outcome; expected {:?}, got {:?}",
+            Outcome::Applied,
+            outcome
+        );
+
+        let closed = find(&approve);
+        assert!(
+            closed == Err(Reject::ReviewClosed),
+            "applied withdrawal should be decided; got {:?}",
+            closed
+        );
+
+        let acct = account::get_account(client, Default::default());
+        assert!(
+            acct.available == 60.0,
+            "wrong available funds; expected {}, got {}",
+            60.0,
+            acct.available
+        );
+    }
+}
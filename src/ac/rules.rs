@@ -0,0 +1,273 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use super::account::{Account, ACCOUNTS};
+use super::history::{self, Activity, Window};
+use crate::error::Error;
+use crate::tx::transaction::Transaction;
+
+/// The loaded risk rules, in file order.
+pub static RULES: Lazy<Mutex<Vec<Rule>>> = Lazy::new(|| Mutex::new(vec![]));
+
+/// What a matching rule does with a transaction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Apply the transaction without looking at later rules.
+    Allow,
+    /// Reject the transaction.
+    Reject,
+    /// Keep the transaction from being applied until someone reviews it.
+    Hold,
+    /// Lock the client and reject the transaction.
+    Lock,
+}
+
+/// A rule matches when every condition it sets holds. Conditions on balances
+/// look at the client's bucket in the transaction currency before it is applied.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Shown in the journal for the rows the rule decided.
+    pub name: String,
+    /// What happens to a transaction the rule matches.
+    pub action: Action,
+    /// Transaction types the rule looks at; all of them when empty.
+    #[serde(default)]
+    pub types: Vec<String>,
+    /// Matches amounts above this.
+    #[serde(default)]
+    pub amount_over: Option<f32>,
+    /// Matches while available funds are below this.
+    #[serde(default)]
+    pub available_below: Option<f32>,
+    /// Matches while held funds are above this.
+    #[serde(default)]
+    pub held_over: Option<f32>,
+    /// Matches after recent activity of the client.
+    #[serde(default)]
+    pub recent: Option<Recent>,
+}
+
+/// Applied transactions of one type the client made shortly before.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Recent {
+    /// The transaction type to look for.
+    pub r#type: String,
+    /// How far back to look, as rows or as a duration.
+    #[serde(deserialize_with = "deserialize_window")]
+    pub within: Window,
+    /// How many such rows it takes; one by default.
+    #[serde(default = "at_least_one")]
+    pub at_least: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+    rules: Vec<Rule>,
+}
+
+impl Rule {
+    /// Whether `tranx` meets every condition, given the bucket and activity of its client.
+    pub fn matches(
+        &self,
+        tranx: &Transaction,
+        acct: Option<&Account>,
+        activity: &Activity,
+    ) -> bool {
+        let available = acct.map_or(0.0, |acct| acct.available);
+        let held = acct.map_or(0.0, |acct| acct.held);
+
+        (self.types.is_empty() || self.types.contains(&tranx.r#type))
+            && self.amount_over.is_none_or(|over| tranx.amount > over)
+            && self.available_below.is_none_or(|below| available < below)
+            && self.held_over.is_none_or(|over| held > over)
+            && self.recent.as_ref().is_none_or(|recent| {
+                activity
+                    .recent(&recent.r#type, recent.within, tranx)
+                    .count()
+                    >= recent.at_least
+            })
+    }
+}
+
+/// Adds the rules of a file to the loaded ones. Rule files are TOML or YAML,
+/// told apart by their extension.
+pub fn load(path: &OsString) -> Result<(), Error> {
+    let text = fs::read_to_string(path)?;
+    let format = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    let rules = parse(&text, &format)?;
+    RULES.lock().unwrap().extend(rules);
+
+    Ok(())
+}
+
+/// Parses a rule file given as `toml` or `yaml` text.
+pub fn parse(text: &str, format: &str) -> Result<Vec<Rule>, Error> {
+    let file: RuleFile = match format {
+        "toml" => toml::from_str(text)?,
+        "yaml" | "yml" => serde_yaml::from_str(text)?,
+        other => {
+            return Err(Error::Invalid(format!(
+                "expected a .toml or .yaml rule file, got .{}",
+                other
+            )))
+        }
+    };
+
+    Ok(file.rules)
+}
+
+/// The loaded rule that decides what happens to the transaction; the first
+/// matching one wins.
+pub fn check(tranx: &Transaction) -> Option<Rule> {
+    let rules = RULES.lock().unwrap();
+    if rules.is_empty() {
+        return None;
+    }
+
+    evaluate(&rules, tranx).cloned()
+}
+
+/// The first of `rules` that matches `tranx`.
+pub fn evaluate<'a>(rules: &'a [Rule], tranx: &Transaction) -> Option<&'a Rule> {
+    let acct: Option<Account> = ACCOUNTS
+        .lock()
+        .unwrap()
+        .read((tranx.client, tranx.currency), |acct| acct.copied());
+
+    history::read(tranx.client, |activity| {
+        rules
+            .iter()
+            .find(|rule| rule.matches(tranx, acct.as_ref(), activity))
+    })
+}
+
+fn deserialize_window<'de, D>(deserializer: D) -> Result<Window, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let window = String::deserialize(deserializer)?;
+
+    Window::parse(&window).map_err(serde::de::Error::custom)
+}
+
+fn at_least_one() -> usize {
+    1
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::{evaluate, parse, Action};
+    use crate::ac::history;
+    use crate::ac::outcome::Outcome;
+    use crate::tx::id::{ClientId, TxId};
+    use crate::tx::transaction::Transaction;
+
+    const TOML: &str = r#"
+        [[rules]]
+        name = "admin"
+        action = "allow"
+        types = ["unlock", "freeze", "close"]
+
+        [[rules]]
+        name = "large deposit"
+        action = "hold"
+        types = ["deposit"]
+        amount_over = 1000.0
+
+        [[rules]]
+        name = "withdrawal after dispute"
+        action = "reject"
+        types = ["withdrawal"]
+        recent = { type = "dispute", within = "3" }
+    "#;
+
+    const YAML: &str = r#"
+        rules:
+          - name: large deposit
+            action: hold
+            types: [deposit]
+            amount_over: 1000.0
+    "#;
+
+    #[test]
+    fn test_rules_parse() {
+        let toml = parse(TOML, "toml").unwrap();
+        let yaml = parse(YAML, "yaml").unwrap();
+
+        assert!(
+            toml.len() == 3 && toml[1].action == Action::Hold && toml[2].recent.is_some(),
+            "wrong toml rules; got {:?}",
+            toml
+        );
+
+        assert!(
+            yaml.len() == 1
+                && yaml[0].name == "large deposit"
+                && yaml[0].amount_over == Some(1000.0),
+            "wrong yaml rules; got {:?}",
+            yaml
+        );
+
+        assert!(
+            parse("rules = [{ name = \"x\", action = \"maybe\" }]", "toml").is_err(),
+            "unknown action should fail"
+        );
+        assert!(parse(YAML, "json").is_err(), "unknown format should fail");
+    }
+
+    #[test]
+    fn test_rules_evaluate() {
+        let rules = parse(TOML, "toml").unwrap();
+
+        let large = Transaction::new("deposit".to_string(), ClientId(190), TxId(1900), 5000.0);
+        let small = Transaction::new("deposit".to_string(), ClientId(190), TxId(1901), 50.0);
+
+        let hit = evaluate(&rules, &large).map(|rule| rule.name.as_str());
+        assert!(
+            hit == Some("large deposit"),
+            "wrong rule hit; expected {}, got {:?}",
+            "large deposit",
+            hit
+        );
+        assert!(
+            evaluate(&rules, &small).is_none(),
+            "small deposit should not hit a rule"
+        );
+
+        history::record(&small, Outcome::Applied);
+        let dispute = Transaction::new("dispute".to_string(), ClientId(190), TxId(1901), 0.0);
+        history::record(&dispute, Outcome::Applied);
+
+        let withdrawal =
+            Transaction::new("withdrawal".to_string(), ClientId(190), TxId(1902), 10.0);
+        let hit = evaluate(&rules, &withdrawal).map(|rule| rule.name.as_str());
+        assert!(
+            hit == Some("withdrawal after dispute"),
+            "wrong rule hit; expected {}, got {:?}",
+            "withdrawal after dispute",
+            hit
+        );
+
+        // the dispute leaves the three row window after two more rows
+        history::record(&small, Outcome::Applied);
+        history::record(&small, Outcome::Applied);
+        assert!(
+            evaluate(&rules, &withdrawal).is_none(),
+            "dispute should have left the window"
+        );
+    }
+}
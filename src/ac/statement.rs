@@ -0,0 +1,364 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::account::{self, Account};
+use super::ledger::{self, Book, Posting};
+use crate::error::Error;
+use crate::tx::currency::Currency;
+use crate::tx::id::{ClientId, TxId};
+use crate::tx::journal::{self, Entry};
+
+/// One row of a client statement. The closing balance rows carry no seq or tx,
+/// and the time of the last row that moved funds in the bucket.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Line {
+    /// The journal sequence number of the row.
+    pub seq: Option<usize>,
+    /// The row type, or `balance` for the closing rows.
+    pub r#type: String,
+    /// The tx id of the row.
+    pub tx: Option<TxId>,
+    /// The row timestamp, or the last update of the bucket on a balance row.
+    pub timestamp: Option<DateTime<Utc>>,
+    /// The currency the line is in.
+    pub currency: Currency,
+    /// Signed effect on the client, or the total on a balance row.
+    pub amount: f32,
+    /// Running available balance.
+    pub available: f32,
+    /// Running held balance.
+    pub held: f32,
+    /// Running total balance.
+    pub total: f32,
+}
+
+/// Builds the statement of a client from the applied journal entries, in order,
+/// with a running balance per currency followed by the closing balances.
+pub fn build(client: ClientId) -> Vec<Line> {
+    let entries: Vec<Entry> = journal::JOURNAL
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|entry| entry.applied)
+        .cloned()
+        .collect();
+
+    // postings of a tx are consumed in the same order its entries were journaled
+    let mut postings: HashMap<TxId, VecDeque<Posting>> = HashMap::new();
+    for posting in ledger::POSTINGS.lock().unwrap().iter() {
+        postings.entry(posting.tx).or_default().push_back(*posting);
+    }
+
+    let mut running: BTreeMap<Currency, Account> = BTreeMap::new();
+    let mut lines = vec![];
+
+    for entry in entries {
+        let queue = postings.entry(entry.tx).or_default();
+        let posted: Vec<Posting> = (0..posting_count(&entry.r#type))
+            .filter_map(|_| queue.pop_front())
+            .collect();
+
+        let touched: Vec<Posting> = posted
+            .into_iter()
+            .filter(|posting| touches(posting, client))
+            .collect();
+
+        // admin transactions move no funds but still belong on the statement
+        if touched.is_empty() && entry.client == client {
+            let acct = running
+                .entry(entry.currency)
+                .or_insert_with(|| opening(client, entry.currency));
+            lines.push(line(&entry, acct, 0.0));
+        }
+
+        for posting in touched {
+            let acct = running
+                .entry(posting.currency)
+                .or_insert_with(|| opening(client, posting.currency));
+            acct.apply(&posting);
+
+            let row = line(&entry, acct, signed(&posting, client));
+            lines.push(row);
+        }
+    }
+
+    for key in account::client_keys(client) {
+        let acct = account::get_account(key.0, key.1);
+        lines.push(Line {
+            seq: None,
+            r#type: "balance".to_string(),
+            tx: None,
+            timestamp: acct.updated,
+            currency: acct.currency,
+            amount: acct.total,
+            available: acct.available,
+            held: acct.held,
+            total: acct.total,
+        });
+    }
+
+    lines
+}
+
+/// Writes the statement of one client to stdout.
+pub fn print(client: ClientId) -> Result<(), Error> {
+    if account::client_keys(client).is_empty() {
+        return Err(Error::Invalid(format!("unknown client {}", client)));
+    }
+
+    let mut csv_writer = csv::Writer::from_writer(io::stdout());
+    for line in build(client) {
+        csv_writer.serialize(line)?;
+    }
+    csv_writer.flush()?;
+
+    Ok(())
+}
+
+/// Writes one statement file per client into `dir`.
+pub fn export_all(dir: &OsString) -> Result<usize, Error> {
+    fs::create_dir_all(dir)?;
+
+    let mut clients: Vec<ClientId> = account::CLIENTS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|key| key.0)
+        .collect();
+    clients.sort();
+    clients.dedup();
+
+    for client in clients.iter() {
+        let path = Path::new(dir).join(format!("client-{}.csv", client));
+        let mut csv_writer = csv::Writer::from_path(path)?;
+        for line in build(*client) {
+            csv_writer.serialize(line)?;
+        }
+        csv_writer.flush()?;
+    }
+
+    Ok(clients.len())
+}
+
+// Postings an applied row of this type makes under its tx id
+fn posting_count(r#type: &str) -> usize {
+    match r#type {
+        // a conversion posts one leg in each currency
+        "convert" => 2,
+        "deposit" | "withdrawal" | "transfer" | "dispute" | "resolve" | "chargeback"
+        | "authorize" | "capture" | "void" | "expire" | "fee" => 1,
+        // review decisions and admin rows move no funds, even when they reuse a tx id
+        _ => 0,
+    }
+}
+
+fn touches(posting: &Posting, client: ClientId) -> bool {
+    [posting.debit, posting.credit]
+        .iter()
+        .any(|book| *book == Book::Available(client) || *book == Book::Held(client))
+}
+
+// Postings that reach the client are positive, those that leave are negative.
+// Moves between the client's own books are shown by their effect on available.
+fn signed(posting: &Posting, client: ClientId) -> f32 {
+    if posting.credit == Book::Available(client) {
+        posting.amount
+    } else if posting.debit == Book::Available(client) {
+        -posting.amount
+    } else if posting.credit == Book::Held(client) {
+        posting.amount
+    } else {
+        -posting.amount
+    }
+}
+
+fn opening(client: ClientId, currency: Currency) -> Account {
+    let mut acct = Account::new(client, 0.0, 0.0);
+    acct.currency = currency;
+
+    acct
+}
+
+fn line(entry: &Entry, acct: &Account, amount: f32) -> Line {
+    Line {
+        seq: Some(entry.seq),
+        r#type: entry.r#type.to_string(),
+        tx: Some(entry.tx),
+        timestamp: entry.timestamp,
+        currency: acct.currency,
+        amount,
+        available: acct.available,
+        held: acct.held,
+        total: acct.total,
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::build;
+    use crate::ac::account;
+    use crate::ac::review;
+    use crate::tx::id::{ClientId, TxId};
+    use crate::tx::timestamp;
+    use crate::tx::transaction::Transaction;
+
+    #[test]
+    fn test_client_statement() {
+        let client = ClientId(140);
+        let mut transfer = Transaction::new("transfer".to_string(), ClientId(141), TxId(1403), 2.0);
+        transfer.to_client = Some(client);
+
+        account::process(&Transaction::new(
+            "deposit".to_string(),
+            ClientId(141),
+            TxId(1400),
+            5.0,
+        ));
+        account::process(&Transaction::new(
+            "deposit".to_string(),
+            client,
+            TxId(1401),
+            10.0,
+        ));
+        account::process(&Transaction::new(
+            "withdrawal".to_string(),
+            client,
+            TxId(1402),
+            4.0,
+        ));
+        account::process(&transfer);
+        account::process(&Transaction::new(
+            "dispute".to_string(),
+            client,
+            TxId(1401),
+            3.0,
+        ));
+        // rejected, not on the statement
+        account::process(&Transaction::new(
+            "withdrawal".to_string(),
+            client,
+            TxId(1404),
+            50.0,
+        ));
+
+        let lines = build(client);
+        let types: Vec<&str> = lines.iter().map(|line| line.r#type.as_str()).collect();
+
+        assert!(
+            types == vec!["deposit", "withdrawal", "transfer", "dispute", "balance"],
+            "wrong statement lines; got {:?}",
+            types
+        );
+
+        let amounts: Vec<f32> = lines.iter().map(|line| line.amount).collect();
+        assert!(
+            amounts == vec![10.0, -4.0, 2.0, -3.0, 8.0],
+            "wrong statement amounts; got {:?}",
+            amounts
+        );
+
+        let dispute = &lines[3];
+        assert!(
+            dispute.available == 5.0 && dispute.held == 3.0 && dispute.total == 8.0,
+            "wrong running balance after dispute; got {:?}",
+            dispute
+        );
+    }
+
+    #[test]
+    fn test_statement_timestamps() {
+        let client = ClientId(142);
+        let mut deposit = Transaction::new("deposit".to_string(), client, TxId(1420), 10.0);
+        let mut funding = Transaction::new("deposit".to_string(), ClientId(143), TxId(1421), 5.0);
+        let mut transfer = Transaction::new("transfer".to_string(), ClientId(143), TxId(1422), 2.0);
+        deposit.timestamp = Some(timestamp::parse("2024-03-01T09:00:00Z").unwrap());
+        funding.timestamp = Some(timestamp::parse("2024-03-01T10:00:00Z").unwrap());
+        transfer.timestamp = Some(timestamp::parse("2024-03-01T11:00:00Z").unwrap());
+        transfer.to_client = Some(client);
+
+        account::process(&deposit);
+        account::process(&funding);
+        account::process(&transfer);
+        // rows without a timestamp leave the last one in place
+        account::process(&Transaction::new(
+            "freeze".to_string(),
+            client,
+            TxId(1423),
+            0.0,
+        ));
+
+        let lines = build(client);
+        let times: Vec<_> = lines.iter().map(|line| line.timestamp).collect();
+
+        assert!(
+            times
+                == vec![
+                    deposit.timestamp,
+                    transfer.timestamp,
+                    None,
+                    transfer.timestamp
+                ],
+            "wrong statement timestamps; got {:?}",
+            times
+        );
+
+        let acct = account::get_account(client, Default::default());
+        assert!(
+            acct.updated == transfer.timestamp,
+            "received transfer should update the bucket; expected {:?}, got {:?}",
+            transfer.timestamp,
+            acct.updated
+        );
+    }
+
+    #[test]
+    fn test_statement_rows_without_postings() {
+        let client = ClientId(144);
+        let deposit = Transaction::new("deposit".to_string(), client, TxId(1440), 10.0);
+        review::park(&deposit, "large deposit");
+
+        // the approval, the freeze and the unlock all carry the deposit's tx id
+        for (r#type, amount) in [
+            ("approve", 0.0),
+            ("freeze", 0.0),
+            ("unlock", 0.0),
+            ("dispute", 3.0),
+        ] {
+            account::process(&Transaction::new(
+                r#type.to_string(),
+                client,
+                TxId(1440),
+                amount,
+            ));
+        }
+
+        let lines = build(client);
+        let types: Vec<&str> = lines.iter().map(|line| line.r#type.as_str()).collect();
+
+        assert!(
+            types == vec!["deposit", "approve", "freeze", "unlock", "dispute", "balance"],
+            "wrong statement lines; got {:?}",
+            types
+        );
+
+        let amounts: Vec<f32> = lines.iter().map(|line| line.amount).collect();
+        assert!(
+            amounts == vec![10.0, 0.0, 0.0, 0.0, -3.0, 10.0],
+            "wrong statement amounts; got {:?}",
+            amounts
+        );
+
+        let dispute = &lines[4];
+        assert!(
+            dispute.available == 7.0 && dispute.held == 3.0 && dispute.total == 10.0,
+            "wrong running balance after dispute; got {:?}",
+            dispute
+        );
+    }
+}
@@ -0,0 +1,277 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::fs::File;
+use std::time::Duration;
+
+use super::account::{self, Account};
+use super::disputes;
+use super::outcome::Reject;
+use crate::error::Error;
+use crate::tx::currency::Currency;
+use crate::tx::journal::{self, Entry};
+use crate::tx::rates::Rounding;
+
+// transaction types whose amount counts towards the volume of a run
+const VOLUME_TYPES: [&str; 4] = ["deposit", "withdrawal", "transfer", "convert"];
+
+/// Outcomes of the rows of one transaction type.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct Counts {
+    /// Rows applied.
+    pub applied: usize,
+    /// Rows rejected.
+    pub rejected: usize,
+    /// Rows held for review.
+    pub pending: usize,
+    /// Rejected rows by reason.
+    pub reasons: BTreeMap<Reject, usize>,
+}
+
+/// Statistics of one run, reported once all rows have been processed.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    /// Rows read from the input.
+    pub rows_read: usize,
+    /// Rows applied.
+    pub applied: usize,
+    /// Rows rejected.
+    pub rejected: usize,
+    /// Rows held for review when they were read.
+    pub pending: usize,
+    /// Outcomes per transaction type.
+    pub types: BTreeMap<String, Counts>,
+    /// Clients locked at the end of the run.
+    pub locked_accounts: usize,
+    /// Disputes still unresolved at the end of the run.
+    pub open_disputes: usize,
+    /// Funds held at the end of the run, per currency.
+    pub held: BTreeMap<Currency, f64>,
+    /// Amount of applied deposits, withdrawals, transfers and conversions, per currency.
+    pub volume: BTreeMap<Currency, f64>,
+    /// How long the run took.
+    pub wall_time_ms: f64,
+    /// Rows read per second.
+    pub throughput: f64,
+}
+
+/// Gathers the statistics from the journal and the accounts.
+pub fn build(rows_read: usize, wall_time: Duration) -> Summary {
+    let entries: Vec<Entry> = journal::JOURNAL.lock().unwrap().to_vec();
+    let mut summary = Summary {
+        rows_read,
+        ..Default::default()
+    };
+    count(&mut summary, &entries);
+
+    let accounts: Vec<Account> = account::CLIENTS
+        .lock()
+        .unwrap()
+        .to_vec()
+        .into_iter()
+        .map(|key| account::get_account(key.0, key.1))
+        .collect();
+
+    // lock state is shared by every currency of a client
+    summary.locked_accounts = accounts
+        .iter()
+        .filter(|acct| acct.locked)
+        .map(|acct| acct.client)
+        .collect::<BTreeSet<_>>()
+        .len();
+
+    for acct in accounts.iter().filter(|acct| acct.held > 0.0) {
+        *summary.held.entry(acct.currency).or_default() += acct.held as f64;
+    }
+
+    summary.open_disputes = disputes::open().len();
+
+    // sums of f32 amounts carry noise beyond the four decimal places we keep
+    for value in summary.held.values_mut().chain(summary.volume.values_mut()) {
+        *value = Rounding::Nearest.apply(*value);
+    }
+
+    summary.wall_time_ms = wall_time.as_secs_f64() * 1000.0;
+    if wall_time.as_secs_f64() > 0.0 {
+        summary.throughput = rows_read as f64 / wall_time.as_secs_f64();
+    }
+
+    summary
+}
+
+// Counts the rows of each type by outcome, and the volume applied per currency
+fn count(summary: &mut Summary, entries: &[Entry]) {
+    for entry in entries {
+        let counts = summary.types.entry(entry.r#type.to_string()).or_default();
+
+        if entry.applied {
+            counts.applied += 1;
+            summary.applied += 1;
+
+            if VOLUME_TYPES.contains(&entry.r#type.as_str()) {
+                *summary.volume.entry(entry.currency).or_default() += entry.amount as f64;
+            }
+        } else if entry.pending {
+            counts.pending += 1;
+            summary.pending += 1;
+        } else {
+            counts.rejected += 1;
+            summary.rejected += 1;
+
+            if let Some(reason) = entry.reason {
+                *counts.reasons.entry(reason).or_default() += 1;
+            }
+        }
+    }
+}
+
+/// Prints the summary to stderr.
+pub fn print(summary: &Summary) {
+    eprintln!("rows read: {}", summary.rows_read);
+    eprintln!(
+        "applied: {}, rejected: {}, pending: {}",
+        summary.applied, summary.rejected, summary.pending
+    );
+
+    for (r#type, counts) in summary.types.iter() {
+        let reasons: Vec<String> = counts
+            .reasons
+            .iter()
+            .map(|(reason, count)| format!("{} {}", reason.as_str(), count))
+            .collect();
+
+        let pending = if counts.pending > 0 {
+            format!(", {} pending", counts.pending)
+        } else {
+            String::new()
+        };
+
+        if reasons.is_empty() {
+            eprintln!(
+                "  {}: {} applied, {} rejected{}",
+                r#type, counts.applied, counts.rejected, pending
+            );
+        } else {
+            eprintln!(
+                "  {}: {} applied, {} rejected{} ({})",
+                r#type,
+                counts.applied,
+                counts.rejected,
+                pending,
+                reasons.join(", ")
+            );
+        }
+    }
+
+    eprintln!("locked accounts: {}", summary.locked_accounts);
+    eprintln!("open disputes: {}", summary.open_disputes);
+
+    for (currency, held) in summary.held.iter() {
+        eprintln!("held {}: {}", currency, held);
+    }
+
+    for (currency, volume) in summary.volume.iter() {
+        eprintln!("volume {}: {}", currency, volume);
+    }
+
+    eprintln!(
+        "wall time: {:.3} ms, throughput: {:.0} rows/s",
+        summary.wall_time_ms, summary.throughput
+    );
+}
+
+/// Writes the summary to `path` as JSON.
+pub fn export_json(summary: &Summary, path: &OsString) -> Result<(), Error> {
+    let file = File::create(path)?;
+    serde_json::to_writer_pretty(file, summary)?;
+
+    Ok(())
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::{build, count, Summary};
+    use crate::ac::account;
+    use crate::ac::outcome::Reject;
+    use crate::tx::currency::Currency;
+    use crate::tx::id::{ClientId, TxId};
+    use crate::tx::journal::{self, Entry};
+    use crate::tx::transaction::Transaction;
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    #[test]
+    fn test_run_summary() {
+        let currency = Currency::new("SUMM").unwrap();
+        let rows = vec![
+            Transaction::new("deposit".to_string(), ClientId(150), TxId(1500), 10.0),
+            Transaction::new("withdrawal".to_string(), ClientId(150), TxId(1501), 4.0),
+            Transaction::new("withdrawal".to_string(), ClientId(150), TxId(1502), 40.0),
+            Transaction::new("dispute".to_string(), ClientId(150), TxId(1500), 2.0),
+        ];
+
+        for mut tranx in rows {
+            tranx.currency = currency;
+            account::process(&tranx);
+        }
+
+        // other tests share the journal, only count the rows in this currency
+        let entries: Vec<Entry> = journal::JOURNAL
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|entry| entry.currency == currency)
+            .cloned()
+            .collect();
+        let mut own = Summary::default();
+        count(&mut own, &entries);
+
+        assert!(
+            own.applied == 3 && own.rejected == 1 && own.pending == 0,
+            "wrong row counts; expected {} applied and {} rejected, got {:?}",
+            3,
+            1,
+            own
+        );
+
+        let withdrawals = &own.types["withdrawal"];
+        assert!(
+            withdrawals.applied == 1
+                && withdrawals.rejected == 1
+                && withdrawals.reasons == BTreeMap::from([(Reject::InsufficientFunds, 1)]),
+            "rejected withdrawal not counted; got {:?}",
+            withdrawals
+        );
+
+        assert!(
+            own.volume[&currency] == 14.0,
+            "wrong volume; expected {}, got {}",
+            14.0,
+            own.volume[&currency]
+        );
+
+        let summary = build(4, Duration::from_millis(2));
+
+        assert!(
+            summary.volume[&currency] == 14.0,
+            "wrong volume; expected {}, got {}",
+            14.0,
+            summary.volume[&currency]
+        );
+
+        assert!(
+            summary.held[&currency] == 2.0,
+            "wrong held funds; expected {}, got {}",
+            2.0,
+            summary.held[&currency]
+        );
+
+        assert!(
+            summary.throughput == 2000.0,
+            "wrong throughput; expected {}, got {}",
+            2000.0,
+            summary.throughput
+        );
+    }
+}
@@ -1,22 +1,28 @@
 #[cfg(test)]
 mod tests {
     use crate::ac::account::{self, Account};
+    use crate::ac::outcome::{Outcome, Reject};
+    use crate::tx::currency::Currency;
+    use crate::tx::id::{ClientId, TxId};
+    use crate::tx::rates;
     use crate::tx::transaction::{self, Dispute, Transaction};
 
     #[test]
     fn test_process_deposit() {
         let tranx_1 = Transaction {
             r#type: "deposit".to_string(),
-            client: 1,
-            tx: 1,
+            client: ClientId(1),
+            tx: TxId(1),
             amount: 10.0,
+            ..Default::default()
         };
 
         let tranx_2 = Transaction {
             r#type: "deposit".to_string(),
-            client: 1,
-            tx: 11,
+            client: ClientId(1),
+            tx: TxId(11),
             amount: 15.0,
+            ..Default::default()
         };
 
         account::process_deposit(&tranx_1);
@@ -24,7 +30,9 @@ mod tests {
         let acct = account::ACCOUNTS
             .lock()
             .unwrap()
-            .read(tranx_1.client, |acc| acc.unwrap().clone());
+            .read((tranx_1.client, Currency::default()), |acc| {
+                acc.unwrap().clone()
+            });
 
         let tranx = transaction::TRANSACTIONS
             .lock()
@@ -85,7 +93,9 @@ mod tests {
         let acct = account::ACCOUNTS
             .lock()
             .unwrap()
-            .read(tranx_2.client, |acc| acc.unwrap().clone());
+            .read((tranx_2.client, Currency::default()), |acc| {
+                acc.unwrap().clone()
+            });
 
         let tranx = transaction::TRANSACTIONS
             .lock()
@@ -146,26 +156,29 @@ mod tests {
 
     #[test]
     fn test_process_withdrawal() {
-        let client = 2;
+        let client = ClientId(2);
         let tranx_withdrawal = Transaction {
             r#type: "withdrawal".to_string(),
             client,
-            tx: 2,
+            tx: TxId(2),
             amount: 10.0,
+            ..Default::default()
         };
 
         let tranx_withdrawal_2 = Transaction {
             r#type: "withdrawal".to_string(),
             client,
-            tx: 22,
+            tx: TxId(22),
             amount: 10.0,
+            ..Default::default()
         };
 
         let tranx_deposit = Transaction {
             r#type: "deposit".to_string(),
             client,
-            tx: 222,
+            tx: TxId(222),
             amount: 15.0,
+            ..Default::default()
         };
 
         account::process_withdrawal(&tranx_withdrawal);
@@ -173,7 +186,7 @@ mod tests {
         let acct = account::ACCOUNTS
             .lock()
             .unwrap()
-            .read(client, |acc| acc.unwrap().clone());
+            .read((client, Currency::default()), |acc| acc.unwrap().clone());
 
         assert!(
             acct.available == 0.0,
@@ -203,30 +216,16 @@ mod tests {
             acct.locked
         );
 
-        let tranx = transaction::TRANSACTIONS
+        // a rejected withdrawal is not stored
+        let stored = transaction::TRANSACTIONS
             .lock()
             .unwrap()
-            .read(tranx_withdrawal.tx, |tranx| tranx.unwrap().clone());
-
-        assert!(
-            tranx.r#type == tranx_withdrawal.r#type,
-            "invalid transaction type funds; expected {}, got {}",
-            tranx_withdrawal.r#type,
-            tranx.r#type
-        );
-
-        assert!(
-            tranx.amount == tranx_withdrawal.amount,
-            "invalid transaction amount funds; expected {}, got {}",
-            tranx_withdrawal.amount,
-            tranx.amount
-        );
+            .exists(tranx_withdrawal.tx);
 
         assert!(
-            tranx.client == tranx_withdrawal.client,
-            "invalid transaction client funds; expected {}, got {}",
-            tranx_withdrawal.client,
-            tranx.client
+            !stored,
+            "rejected withdrawal stored; expected {}, got {}",
+            false, stored
         );
 
         account::process_deposit(&tranx_deposit);
@@ -235,7 +234,7 @@ mod tests {
         let acct = account::ACCOUNTS
             .lock()
             .unwrap()
-            .read(client, |acc| acc.unwrap().clone());
+            .read((client, Currency::default()), |acc| acc.unwrap().clone());
         let amount_diff = tranx_deposit.amount - tranx_withdrawal_2.amount;
         assert!(
             acct.available == amount_diff,
@@ -264,28 +263,54 @@ mod tests {
             false,
             acct.locked
         );
+
+        let tranx = transaction::TRANSACTIONS
+            .lock()
+            .unwrap()
+            .read(tranx_withdrawal_2.tx, |tranx| tranx.unwrap().clone());
+
+        assert!(
+            tranx.r#type == tranx_withdrawal_2.r#type,
+            "invalid transaction type funds; expected {}, got {}",
+            tranx_withdrawal_2.r#type,
+            tranx.r#type
+        );
+
+        assert!(
+            tranx.amount == tranx_withdrawal_2.amount,
+            "invalid transaction amount funds; expected {}, got {}",
+            tranx_withdrawal_2.amount,
+            tranx.amount
+        );
+
+        assert!(
+            tranx.client == tranx_withdrawal_2.client,
+            "invalid transaction client funds; expected {}, got {}",
+            tranx_withdrawal_2.client,
+            tranx.client
+        );
     }
 
     #[test]
     fn test_process_dispute() {
-        let tranx_dispute = Transaction::new("dispute".to_string(), 3, 33, 0.0);
+        let tranx_dispute = Transaction::new("dispute".to_string(), ClientId(3), TxId(33), 0.0);
 
-        let tranx_deposit = Transaction::new("deposit".to_string(), 3, 3, 15.0);
-        let tranx_deposit_2 = Transaction::new("deposit".to_string(), 3, 33, 10.0);
+        let tranx_deposit = Transaction::new("deposit".to_string(), ClientId(3), TxId(3), 15.0);
+        let tranx_deposit_2 = Transaction::new("deposit".to_string(), ClientId(3), TxId(33), 10.0);
 
         account::process_dispute(&tranx_dispute);
 
-        account::ACCOUNTS
-            .lock()
-            .unwrap()
-            .read(tranx_dispute.client, |acc| {
+        account::ACCOUNTS.lock().unwrap().read(
+            (tranx_dispute.client, Currency::default()),
+            |acc| {
                 assert!(
                     acc == None,
                     "invalid available funds; expected {}, got {:?}",
                     "None",
                     acc
                 );
-            });
+            },
+        );
 
         account::process_deposit(&tranx_deposit);
         account::process_deposit(&tranx_deposit_2);
@@ -294,7 +319,9 @@ mod tests {
         let acct = account::ACCOUNTS
             .lock()
             .unwrap()
-            .read(tranx_dispute.client, |acc| acc.unwrap().clone());
+            .read((tranx_dispute.client, Currency::default()), |acc| {
+                acc.unwrap().clone()
+            });
         assert!(
             acct.available == tranx_deposit.amount,
             "invalid available funds; expected {}, got {}",
@@ -346,25 +373,25 @@ mod tests {
 
     #[test]
     fn test_process_resolve() {
-        let tranx_dispute = Transaction::new("dispute".to_string(), 4, 44, 0.0);
-        let tranx_resolve = Transaction::new("resolve".to_string(), 4, 44, 0.0);
+        let tranx_dispute = Transaction::new("dispute".to_string(), ClientId(4), TxId(44), 0.0);
+        let tranx_resolve = Transaction::new("resolve".to_string(), ClientId(4), TxId(44), 0.0);
 
-        let tranx_deposit = Transaction::new("deposit".to_string(), 4, 4, 15.0);
-        let tranx_deposit_2 = Transaction::new("deposit".to_string(), 4, 44, 10.0);
+        let tranx_deposit = Transaction::new("deposit".to_string(), ClientId(4), TxId(4), 15.0);
+        let tranx_deposit_2 = Transaction::new("deposit".to_string(), ClientId(4), TxId(44), 10.0);
 
         account::process_resolve(&tranx_resolve);
 
-        account::ACCOUNTS
-            .lock()
-            .unwrap()
-            .read(tranx_dispute.client, |acc| {
+        account::ACCOUNTS.lock().unwrap().read(
+            (tranx_dispute.client, Currency::default()),
+            |acc| {
                 assert!(
                     acc == None,
                     "invalid available funds; expected {}, got {:?}",
                     "None",
                     acc
                 );
-            });
+            },
+        );
 
         account::process_deposit(&tranx_deposit);
         account::process_deposit(&tranx_deposit_2);
@@ -373,7 +400,9 @@ mod tests {
         let acct = account::ACCOUNTS
             .lock()
             .unwrap()
-            .read(tranx_dispute.client, |acc| acc.unwrap().clone());
+            .read((tranx_dispute.client, Currency::default()), |acc| {
+                acc.unwrap().clone()
+            });
         assert!(
             acct.available == tranx_deposit.amount,
             "invalid available funds; expected {}, got {}",
@@ -434,7 +463,9 @@ mod tests {
         let acct = account::ACCOUNTS
             .lock()
             .unwrap()
-            .read(tranx_dispute.client, |acc| acc.unwrap().clone());
+            .read((tranx_dispute.client, Currency::default()), |acc| {
+                acc.unwrap().clone()
+            });
 
         let available = tranx_deposit.amount + tranx_deposit_2.amount;
         assert!(
@@ -495,26 +526,27 @@ mod tests {
 
     #[test]
     fn test_process_chargeback() {
-        let tranx_dispute = Transaction::new("dispute".to_string(), 5, 55, 0.0);
-        let tranx_chargeback = Transaction::new("chargeback".to_string(), 5, 55, 0.0);
+        let tranx_dispute = Transaction::new("dispute".to_string(), ClientId(5), TxId(55), 0.0);
+        let tranx_chargeback =
+            Transaction::new("chargeback".to_string(), ClientId(5), TxId(55), 0.0);
 
-        let tranx_deposit = Transaction::new("deposit".to_string(), 5, 5, 15.0);
-        let tranx_deposit_2 = Transaction::new("deposit".to_string(), 5, 55, 10.0);
+        let tranx_deposit = Transaction::new("deposit".to_string(), ClientId(5), TxId(5), 15.0);
+        let tranx_deposit_2 = Transaction::new("deposit".to_string(), ClientId(5), TxId(55), 10.0);
 
         // test not existing dispute
         account::process_chargeback(&tranx_chargeback);
 
-        account::ACCOUNTS
-            .lock()
-            .unwrap()
-            .read(tranx_dispute.client, |acc| {
+        account::ACCOUNTS.lock().unwrap().read(
+            (tranx_dispute.client, Currency::default()),
+            |acc| {
                 assert!(
                     acc == None,
                     "invalid available funds; expected {}, got {:?}",
                     "None",
                     acc
                 );
-            });
+            },
+        );
 
         account::process_deposit(&tranx_deposit);
         account::process_deposit(&tranx_deposit_2);
@@ -523,7 +555,9 @@ mod tests {
         let acct = account::ACCOUNTS
             .lock()
             .unwrap()
-            .read(tranx_dispute.client, |acc| acc.unwrap().clone());
+            .read((tranx_dispute.client, Currency::default()), |acc| {
+                acc.unwrap().clone()
+            });
         assert!(
             acct.available == tranx_deposit.amount,
             "invalid available funds; expected {}, got {}",
@@ -584,7 +618,9 @@ mod tests {
         let acct = account::ACCOUNTS
             .lock()
             .unwrap()
-            .read(tranx_dispute.client, |acc| acc.unwrap().clone());
+            .read((tranx_dispute.client, Currency::default()), |acc| {
+                acc.unwrap().clone()
+            });
 
         assert!(
             acct.available == tranx_deposit.amount,
@@ -643,12 +679,13 @@ mod tests {
 
     #[test]
     fn test_account_deposit() {
-        let mut account = Account::new(1, 20.0, 0.0);
+        let mut account = Account::new(ClientId(1), 20.0, 0.0);
         let tranx = Transaction {
             r#type: "deposit".to_string(),
-            client: 1,
-            tx: 1,
+            client: ClientId(1),
+            tx: TxId(1),
             amount: 15.0,
+            ..Default::default()
         };
 
         // Test initial funds
@@ -714,12 +751,13 @@ mod tests {
 
     #[test]
     fn test_account_withdraw() {
-        let mut account = Account::new(1, 20.0, 0.0);
+        let mut account = Account::new(ClientId(1), 20.0, 0.0);
         let mut tranx = Transaction {
             r#type: "deposit".to_string(),
-            client: 1,
-            tx: 1,
+            client: ClientId(1),
+            tx: TxId(1),
             amount: 15.0,
+            ..Default::default()
         };
 
         // Test initial funds
@@ -821,12 +859,13 @@ mod tests {
 
     #[test]
     fn test_account_dispute() {
-        let mut account = Account::new(1, 20.0, 0.0);
+        let mut account = Account::new(ClientId(1), 20.0, 0.0);
         let mut tranx = Transaction {
             r#type: "dispute".to_string(),
-            client: 1,
-            tx: 1,
+            client: ClientId(1),
+            tx: TxId(1),
             amount: 15.0,
+            ..Default::default()
         };
 
         // Test initial funds
@@ -928,8 +967,8 @@ mod tests {
 
     #[test]
     fn test_account_resolve() {
-        let mut account = Account::new(1, 0.0, 20.0);
-        let mut tranx_deposit = Transaction::new("deposit".to_string(), 1, 1, 20.0);
+        let mut account = Account::new(ClientId(1), 0.0, 20.0);
+        let mut tranx_deposit = Transaction::new("deposit".to_string(), ClientId(1), TxId(1), 20.0);
 
         // Test initial funds
         assert!(
@@ -1030,8 +1069,8 @@ mod tests {
 
     #[test]
     fn test_account_chargeback() {
-        let mut account = Account::new(1, 0.0, 20.0);
-        let mut tranx_deposit = Transaction::new("deposit".to_string(), 1, 1, 20.0);
+        let mut account = Account::new(ClientId(1), 0.0, 20.0);
+        let mut tranx_deposit = Transaction::new("deposit".to_string(), ClientId(1), TxId(1), 20.0);
 
         // Test initial funds
         assert!(
@@ -1129,4 +1168,605 @@ mod tests {
             account.locked
         );
     }
+
+    #[test]
+    fn test_process_account_lifecycle() {
+        let client = ClientId(6);
+        let tranx_deposit = Transaction::new("deposit".to_string(), client, TxId(6), 15.0);
+        let tranx_dispute = Transaction::new("dispute".to_string(), client, TxId(6), 0.0);
+        let tranx_chargeback = Transaction::new("chargeback".to_string(), client, TxId(6), 0.0);
+        let tranx_deposit_2 = Transaction::new("deposit".to_string(), client, TxId(66), 10.0);
+        let tranx_deposit_3 = Transaction::new("deposit".to_string(), client, TxId(666), 10.0);
+
+        let unlock = Transaction::new("unlock".to_string(), client, TxId(6000), 0.0);
+        let freeze = Transaction::new("freeze".to_string(), client, TxId(6001), 0.0);
+        let close = Transaction::new("close".to_string(), client, TxId(6002), 0.0);
+
+        // admin operations need an existing account
+        let outcome = account::process(&unlock);
+        assert!(
+            outcome == Outcome::Rejected(Reject::UnknownClient),
+            "wrong outcome; expect {:?}, got {:?}",
+            Reject::UnknownClient,
+            outcome
+        );
+
+        account::process(&tranx_deposit);
+        account::process(&tranx_dispute);
+        account::process(&tranx_chargeback);
+
+        let outcome = account::process(&tranx_deposit_2);
+        assert!(
+            outcome == Outcome::Rejected(Reject::AccountLocked),
+            "wrong outcome; expect {:?}, got {:?}",
+            Reject::AccountLocked,
+            outcome
+        );
+
+        let outcome = account::process(&unlock);
+        assert!(
+            outcome.is_applied(),
+            "unlock not applied; got {:?}",
+            outcome
+        );
+
+        let outcome = account::process(&tranx_deposit_2);
+        assert!(
+            outcome.is_applied(),
+            "deposit not applied after unlock; got {:?}",
+            outcome
+        );
+
+        account::process(&freeze);
+
+        let acct = account::ACCOUNTS
+            .lock()
+            .unwrap()
+            .read((client, Currency::default()), |acc| *acc.unwrap());
+
+        assert!(
+            acct.locked,
+            "wrong locked status; expect {}, got {}",
+            true, acct.locked
+        );
+
+        account::process(&unlock);
+        account::process(&close);
+
+        let tranx_withdrawal = Transaction::new("withdrawal".to_string(), client, TxId(6667), 5.0);
+        let mut tranx_transfer = Transaction::new("transfer".to_string(), client, TxId(6668), 5.0);
+        tranx_transfer.to_client = Some(ClientId(5));
+
+        // a closed account takes no transactions, in or out
+        for tranx in [&tranx_deposit_3, &tranx_withdrawal, &tranx_transfer] {
+            let outcome = account::process(tranx);
+            assert!(
+                outcome == Outcome::Rejected(Reject::AccountClosed),
+                "wrong {} outcome; expect {:?}, got {:?}",
+                tranx.r#type,
+                Reject::AccountClosed,
+                outcome
+            );
+        }
+
+        let acct = account::ACCOUNTS
+            .lock()
+            .unwrap()
+            .read((client, Currency::default()), |acc| *acc.unwrap());
+
+        assert!(
+            acct.closed,
+            "wrong closed status; expect {}, got {}",
+            true, acct.closed
+        );
+
+        assert!(
+            acct.available == tranx_deposit_2.amount,
+            "invalid available funds; expected {}, got {}",
+            tranx_deposit_2.amount,
+            acct.available
+        );
+
+        let outcome = account::process(&unlock);
+        assert!(
+            outcome == Outcome::Rejected(Reject::AccountClosed),
+            "wrong outcome; expect {:?}, got {:?}",
+            Reject::AccountClosed,
+            outcome
+        );
+    }
+
+    #[test]
+    fn test_process_transfer() {
+        let (source, destination) = (ClientId(7), ClientId(8));
+        let tranx_deposit = Transaction::new("deposit".to_string(), source, TxId(7), 15.0);
+
+        let mut tranx_transfer = Transaction::new("transfer".to_string(), source, TxId(77), 10.0);
+        tranx_transfer.to_client = Some(destination);
+
+        let mut tranx_overdraw = Transaction::new("transfer".to_string(), source, TxId(777), 10.0);
+        tranx_overdraw.to_client = Some(destination);
+
+        let tranx_dispute = Transaction::new("dispute".to_string(), source, TxId(77), 0.0);
+        let tranx_chargeback = Transaction::new("chargeback".to_string(), source, TxId(77), 0.0);
+
+        account::process(&tranx_deposit);
+
+        let outcome = account::process(&tranx_transfer);
+        assert!(
+            outcome.is_applied(),
+            "transfer not applied; got {:?}",
+            outcome
+        );
+
+        let outcome = account::process(&tranx_overdraw);
+        assert!(
+            outcome == Outcome::Rejected(Reject::InsufficientFunds),
+            "wrong outcome; expect {:?}, got {:?}",
+            Reject::InsufficientFunds,
+            outcome
+        );
+
+        let src = account::ACCOUNTS
+            .lock()
+            .unwrap()
+            .read((source, Currency::default()), |acc| *acc.unwrap());
+        let dst = account::ACCOUNTS
+            .lock()
+            .unwrap()
+            .read((destination, Currency::default()), |acc| *acc.unwrap());
+
+        assert!(
+            src.available == 5.0 && src.total == 5.0,
+            "invalid source funds; expected {}, got {}",
+            5.0,
+            src.available
+        );
+
+        assert!(
+            dst.available == 10.0 && dst.total == 10.0,
+            "invalid destination funds; expected {}, got {}",
+            10.0,
+            dst.available
+        );
+
+        // disputes on a transfer hold funds in the receiving account
+        account::process(&tranx_dispute);
+
+        let dst = account::ACCOUNTS
+            .lock()
+            .unwrap()
+            .read((destination, Currency::default()), |acc| *acc.unwrap());
+
+        assert!(
+            dst.held == 10.0 && dst.available == 0.0,
+            "invalid held funds; expected {}, got {}",
+            10.0,
+            dst.held
+        );
+
+        account::process(&tranx_chargeback);
+
+        let src = account::ACCOUNTS
+            .lock()
+            .unwrap()
+            .read((source, Currency::default()), |acc| *acc.unwrap());
+        let dst = account::ACCOUNTS
+            .lock()
+            .unwrap()
+            .read((destination, Currency::default()), |acc| *acc.unwrap());
+
+        assert!(
+            dst.total == 0.0 && dst.locked,
+            "invalid destination after chargeback; expected total {}, got {}",
+            0.0,
+            dst.total
+        );
+
+        assert!(
+            src.available == 15.0 && !src.locked,
+            "transfer not returned to sender; expected {}, got {}",
+            15.0,
+            src.available
+        );
+    }
+
+    #[test]
+    fn test_process_transfer_invalid_amount() {
+        let (source, destination) = (ClientId(14), ClientId(15));
+        let tranx_deposit = Transaction::new("deposit".to_string(), destination, TxId(14), 100.0);
+
+        let mut tranx_negative = Transaction::new("transfer".to_string(), source, TxId(144), -50.0);
+        tranx_negative.to_client = Some(destination);
+
+        let mut tranx_zero = Transaction::new("transfer".to_string(), source, TxId(1444), 0.0);
+        tranx_zero.to_client = Some(destination);
+
+        account::process(&tranx_deposit);
+
+        for tranx in [&tranx_negative, &tranx_zero] {
+            let outcome = account::process(tranx);
+            assert!(
+                outcome == Outcome::Rejected(Reject::InvalidAmount),
+                "wrong outcome; expect {:?}, got {:?}",
+                Reject::InvalidAmount,
+                outcome
+            );
+        }
+
+        let dst = account::get_account(destination, Currency::default());
+        assert!(
+            dst.available == 100.0 && dst.total == 100.0,
+            "receiving account should be untouched; expected {}, got {}",
+            100.0,
+            dst.available
+        );
+
+        let src = account::get_account(source, Currency::default());
+        assert!(
+            src.available == 0.0 && src.total == 0.0,
+            "sending account should be untouched; expected {}, got {}",
+            0.0,
+            src.available
+        );
+    }
+
+    #[test]
+    fn test_process_partial_dispute() {
+        let client = ClientId(9);
+        let tranx_deposit = Transaction::new("deposit".to_string(), client, TxId(9), 10.0);
+        let tranx_dispute = Transaction::new("dispute".to_string(), client, TxId(9), 4.0);
+        let tranx_dispute_2 = Transaction::new("dispute".to_string(), client, TxId(9), 7.0);
+        let tranx_dispute_3 = Transaction::new("dispute".to_string(), client, TxId(9), 6.0);
+        let tranx_resolve = Transaction::new("resolve".to_string(), client, TxId(9), 3.0);
+        let tranx_chargeback = Transaction::new("chargeback".to_string(), client, TxId(9), 0.0);
+
+        account::process(&tranx_deposit);
+        account::process(&tranx_dispute);
+
+        let acct = account::ACCOUNTS
+            .lock()
+            .unwrap()
+            .read((client, Currency::default()), |acc| *acc.unwrap());
+
+        assert!(
+            acct.held == 4.0 && acct.available == 6.0,
+            "invalid held funds; expected {}, got {}",
+            4.0,
+            acct.held
+        );
+
+        // only 6.0 of the deposit is left undisputed
+        let outcome = account::process(&tranx_dispute_2);
+        assert!(
+            outcome == Outcome::Rejected(Reject::ExceedsUndisputed),
+            "wrong outcome; expect {:?}, got {:?}",
+            Reject::ExceedsUndisputed,
+            outcome
+        );
+
+        account::process(&tranx_dispute_3);
+        account::process(&tranx_resolve);
+
+        let dispute: Dispute = transaction::DISPUTES
+            .lock()
+            .unwrap()
+            .read(tranx_dispute.tx, |dis| *dis.unwrap());
+
+        assert!(
+            dispute.amount == 10.0 && dispute.held == 7.0 && !dispute.resolved,
+            "invalid dispute; expected held {}, got {}",
+            7.0,
+            dispute.held
+        );
+
+        account::process(&tranx_chargeback);
+
+        let acct = account::ACCOUNTS
+            .lock()
+            .unwrap()
+            .read((client, Currency::default()), |acc| *acc.unwrap());
+
+        assert!(
+            acct.available == 3.0 && acct.held == 0.0 && acct.total == 3.0,
+            "invalid funds after chargeback; expected {}, got {}",
+            3.0,
+            acct.total
+        );
+
+        assert!(
+            acct.locked,
+            "wrong locked status; expect {}, got {}",
+            true, acct.locked
+        );
+
+        let dispute: Dispute = transaction::DISPUTES
+            .lock()
+            .unwrap()
+            .read(tranx_dispute.tx, |dis| *dis.unwrap());
+
+        assert!(
+            dispute.resolved,
+            "invalid dispute status; expected {}, got {}",
+            true, dispute.resolved
+        );
+    }
+
+    #[test]
+    fn test_process_negative_dispute() {
+        let client = ClientId(16);
+        let tranx_deposit = Transaction::new("deposit".to_string(), client, TxId(16), 10.0);
+        let tranx_dispute = Transaction::new("dispute".to_string(), client, TxId(16), 4.0);
+
+        account::process(&tranx_deposit);
+
+        let outcome = account::process(&Transaction::new(
+            "dispute".to_string(),
+            client,
+            TxId(16),
+            -3.0,
+        ));
+        assert!(
+            outcome == Outcome::Rejected(Reject::InvalidAmount),
+            "wrong outcome; expect {:?}, got {:?}",
+            Reject::InvalidAmount,
+            outcome
+        );
+
+        account::process(&tranx_dispute);
+
+        for r#type in ["resolve", "chargeback"] {
+            let outcome = account::process(&Transaction::new(
+                r#type.to_string(),
+                client,
+                TxId(16),
+                -1.0,
+            ));
+            assert!(
+                outcome == Outcome::Rejected(Reject::InvalidAmount),
+                "wrong {} outcome; expect {:?}, got {:?}",
+                r#type,
+                Reject::InvalidAmount,
+                outcome
+            );
+        }
+
+        let acct = account::get_account(client, Currency::default());
+        assert!(
+            acct.available == 6.0 && acct.held == 4.0 && !acct.locked,
+            "only the positive dispute should hold funds; expected {}, got {}",
+            4.0,
+            acct.held
+        );
+    }
+
+    #[test]
+    fn test_process_rejected_withdrawal_dispute() {
+        let client = ClientId(17);
+        let tranx_deposit = Transaction::new("deposit".to_string(), client, TxId(17), 10.0);
+        let tranx_withdrawal = Transaction::new("withdrawal".to_string(), client, TxId(177), 50.0);
+        let tranx_dispute = Transaction::new("dispute".to_string(), client, TxId(177), 8.0);
+        let tranx_chargeback = Transaction::new("chargeback".to_string(), client, TxId(177), 0.0);
+
+        account::process(&tranx_deposit);
+
+        let outcome = account::process(&tranx_withdrawal);
+        assert!(
+            outcome == Outcome::Rejected(Reject::InsufficientFunds),
+            "wrong outcome; expect {:?}, got {:?}",
+            Reject::InsufficientFunds,
+            outcome
+        );
+
+        // money that never left cannot be disputed or charged back
+        let outcome = account::process(&tranx_dispute);
+        assert!(
+            outcome == Outcome::Rejected(Reject::UnknownTx),
+            "wrong outcome; expect {:?}, got {:?}",
+            Reject::UnknownTx,
+            outcome
+        );
+
+        let outcome = account::process(&tranx_chargeback);
+        assert!(
+            outcome == Outcome::Rejected(Reject::UnknownTx),
+            "wrong outcome; expect {:?}, got {:?}",
+            Reject::UnknownTx,
+            outcome
+        );
+
+        let acct = account::get_account(client, Currency::default());
+        assert!(
+            acct.available == 10.0 && acct.held == 0.0 && acct.total == 10.0 && !acct.locked,
+            "account should be untouched; expected {}, got {:?}",
+            10.0,
+            acct
+        );
+    }
+
+    #[test]
+    fn test_process_multi_currency() {
+        let client = ClientId(10);
+        let eur = Currency::new("EUR").unwrap();
+
+        let tranx_deposit = Transaction::new("deposit".to_string(), client, TxId(10), 10.0);
+        let mut tranx_deposit_eur =
+            Transaction::new("deposit".to_string(), client, TxId(1100), 5.0);
+        tranx_deposit_eur.currency = eur;
+
+        // dispute rows apply in the currency of the disputed transaction
+        let tranx_dispute = Transaction::new("dispute".to_string(), client, TxId(1100), 0.0);
+        let tranx_chargeback = Transaction::new("chargeback".to_string(), client, TxId(1100), 0.0);
+
+        account::process(&tranx_deposit);
+        account::process(&tranx_deposit_eur);
+        account::process(&tranx_dispute);
+
+        let usd_acct = account::ACCOUNTS
+            .lock()
+            .unwrap()
+            .read((client, Currency::default()), |acc| *acc.unwrap());
+        let eur_acct = account::ACCOUNTS
+            .lock()
+            .unwrap()
+            .read((client, eur), |acc| *acc.unwrap());
+
+        assert!(
+            usd_acct.available == 10.0 && usd_acct.held == 0.0,
+            "invalid usd funds; expected {}, got {}",
+            10.0,
+            usd_acct.available
+        );
+
+        assert!(
+            eur_acct.available == 0.0 && eur_acct.held == 5.0,
+            "invalid eur held funds; expected {}, got {}",
+            5.0,
+            eur_acct.held
+        );
+
+        account::process(&tranx_chargeback);
+
+        let usd_acct = account::ACCOUNTS
+            .lock()
+            .unwrap()
+            .read((client, Currency::default()), |acc| *acc.unwrap());
+
+        assert!(
+            usd_acct.locked && usd_acct.total == 10.0,
+            "chargeback should lock every currency; expected {}, got {}",
+            true,
+            usd_acct.locked
+        );
+
+        assert!(
+            account::client_keys(client).len() == 2,
+            "wrong number of currency buckets; expected {}, got {}",
+            2,
+            account::client_keys(client).len()
+        );
+    }
+
+    #[test]
+    fn test_process_convert() {
+        let client = ClientId(11);
+        let gbp = Currency::new("GBP").unwrap();
+        let jpy = Currency::new("JPY").unwrap();
+
+        rates::RATES.lock().unwrap().insert((gbp, jpy), 150.5);
+
+        let mut tranx_deposit = Transaction::new("deposit".to_string(), client, TxId(1110), 10.0);
+        tranx_deposit.currency = gbp;
+
+        let mut tranx_convert = Transaction::new("convert".to_string(), client, TxId(1111), 2.0);
+        tranx_convert.currency = gbp;
+        tranx_convert.to_currency = Some(jpy);
+
+        // there is no rate back from yen
+        let mut tranx_convert_back =
+            Transaction::new("convert".to_string(), client, TxId(1112), 100.0);
+        tranx_convert_back.currency = jpy;
+        tranx_convert_back.to_currency = Some(gbp);
+
+        let mut tranx_convert_negative =
+            Transaction::new("convert".to_string(), client, TxId(1113), -2.0);
+        tranx_convert_negative.currency = gbp;
+        tranx_convert_negative.to_currency = Some(jpy);
+
+        account::process(&tranx_deposit);
+
+        let outcome = account::process(&tranx_convert);
+        assert!(
+            outcome.is_applied(),
+            "conversion not applied; got {:?}",
+            outcome
+        );
+
+        let outcome = account::process(&tranx_convert_back);
+        assert!(
+            outcome == Outcome::Rejected(Reject::UnknownRate),
+            "wrong outcome; expect {:?}, got {:?}",
+            Reject::UnknownRate,
+            outcome
+        );
+
+        let outcome = account::process(&tranx_convert_negative);
+        assert!(
+            outcome == Outcome::Rejected(Reject::InvalidAmount),
+            "wrong outcome; expect {:?}, got {:?}",
+            Reject::InvalidAmount,
+            outcome
+        );
+
+        let gbp_acct = account::ACCOUNTS
+            .lock()
+            .unwrap()
+            .read((client, gbp), |acc| *acc.unwrap());
+        let jpy_acct = account::ACCOUNTS
+            .lock()
+            .unwrap()
+            .read((client, jpy), |acc| *acc.unwrap());
+
+        assert!(
+            gbp_acct.available == 8.0,
+            "invalid source funds; expected {}, got {}",
+            8.0,
+            gbp_acct.available
+        );
+
+        assert!(
+            jpy_acct.available == 301.0 && jpy_acct.total == 301.0,
+            "invalid converted funds; expected {}, got {}",
+            301.0,
+            jpy_acct.available
+        );
+    }
+
+    #[test]
+    fn test_process_duplicate_tx() {
+        let client = ClientId(12);
+        let tranx_deposit = Transaction::new("deposit".to_string(), client, TxId(12), 10.0);
+        let tranx_replay = tranx_deposit.clone();
+        let tranx_conflict = Transaction::new("deposit".to_string(), client, TxId(12), 99.0);
+        let tranx_other_client =
+            Transaction::new("deposit".to_string(), ClientId(13), TxId(12), 10.0);
+
+        account::process(&tranx_deposit);
+
+        let outcome = account::process(&tranx_replay);
+        assert!(
+            outcome == Outcome::Rejected(Reject::DuplicateTx),
+            "wrong outcome; expect {:?}, got {:?}",
+            Reject::DuplicateTx,
+            outcome
+        );
+
+        let outcome = account::process(&tranx_conflict);
+        assert!(
+            outcome == Outcome::Rejected(Reject::ConflictingTx),
+            "wrong outcome; expect {:?}, got {:?}",
+            Reject::ConflictingTx,
+            outcome
+        );
+
+        let outcome = account::process(&tranx_other_client);
+        assert!(
+            outcome == Outcome::Rejected(Reject::ConflictingTx),
+            "wrong outcome; expect {:?}, got {:?}",
+            Reject::ConflictingTx,
+            outcome
+        );
+
+        let acct = account::ACCOUNTS
+            .lock()
+            .unwrap()
+            .read((client, Currency::default()), |acc| *acc.unwrap());
+
+        assert!(
+            acct.total == tranx_deposit.amount,
+            "invalid total funds; expected {}, got {}",
+            tranx_deposit.amount,
+            acct.total
+        );
+    }
 }
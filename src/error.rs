@@ -0,0 +1,80 @@
+use std::fmt;
+use std::io;
+
+/// Why loading an input file or writing a report failed.
+///
+/// Only the file-level functions (loaders and exports) return it. A
+/// transaction the engine will not apply is an [`Outcome`](crate::Outcome),
+/// not an error.
+#[derive(Debug)]
+pub enum Error {
+    /// The file could not be opened, read or written.
+    Io(io::Error),
+    /// A CSV row could not be read or written.
+    Csv(csv::Error),
+    /// A JSON report could not be written.
+    Json(serde_json::Error),
+    /// A TOML rule file could not be parsed.
+    Toml(toml::de::Error),
+    /// A YAML rule file could not be parsed.
+    Yaml(serde_yaml::Error),
+    /// The input parsed but its content is not acceptable, e.g. a negative
+    /// fee, a rate that creates value or a trial balance that does not net.
+    Invalid(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => err.fmt(f),
+            Error::Csv(err) => err.fmt(f),
+            Error::Json(err) => err.fmt(f),
+            Error::Toml(err) => err.fmt(f),
+            Error::Yaml(err) => err.fmt(f),
+            Error::Invalid(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Csv(err) => Some(err),
+            Error::Json(err) => Some(err),
+            Error::Toml(err) => Some(err),
+            Error::Yaml(err) => Some(err),
+            Error::Invalid(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(err: csv::Error) -> Self {
+        Error::Csv(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Self {
+        Error::Toml(err)
+    }
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(err: serde_yaml::Error) -> Self {
+        Error::Yaml(err)
+    }
+}
@@ -0,0 +1,206 @@
+use async_stream::stream;
+use futures_util::{Stream, StreamExt};
+use std::pin::Pin;
+use tokio::net::TcpListener;
+use tokio::task;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::ac::account::{self, Account};
+use crate::ac::events::{self, Missed};
+use crate::ac::outcome::Outcome;
+use crate::tx::currency::Currency;
+use crate::tx::id::{ClientId, TxId};
+use crate::tx::timestamp;
+use crate::tx::transaction::Transaction;
+
+/// Types generated from `proto/processor.proto`.
+// the proto file has no comments for the generated code to carry
+#[allow(missing_docs)]
+pub mod pb {
+    tonic::include_proto!("processor");
+}
+
+pub use pb::processor_client::ProcessorClient;
+pub use pb::processor_server::ProcessorServer;
+
+/// The address the service binds to unless told otherwise.
+pub const DEFAULT_BIND: &str = "127.0.0.1:50051";
+
+type ResponseStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+/// The gRPC service, handing every request to the engine.
+#[derive(Debug, Default)]
+pub struct Processor {}
+
+/// Serves the gRPC API until ctrl-c is received.
+pub async fn serve(listener: TcpListener) -> Result<(), tonic::transport::Error> {
+    tonic::transport::Server::builder()
+        .add_service(ProcessorServer::new(Processor::default()))
+        .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+        .await
+}
+
+#[tonic::async_trait]
+impl pb::processor_server::Processor for Processor {
+    type StreamTransactionsStream = ResponseStream<pb::Outcome>;
+    type ListAccountsStream = ResponseStream<pb::Account>;
+    type WatchAccountsStream = ResponseStream<pb::Account>;
+
+    async fn submit(
+        &self,
+        request: Request<pb::Transaction>,
+    ) -> Result<Response<pb::Outcome>, Status> {
+        let tranx = transaction(request.into_inner()).map_err(Status::invalid_argument)?;
+
+        Ok(Response::new(process(tranx).await?))
+    }
+
+    async fn stream_transactions(
+        &self,
+        request: Request<Streaming<pb::Transaction>>,
+    ) -> Result<Response<Self::StreamTransactionsStream>, Status> {
+        let mut incoming = request.into_inner();
+
+        let outcomes = stream! {
+            while let Some(received) = incoming.next().await {
+                let tranx = transaction(received?).map_err(Status::invalid_argument)?;
+
+                yield process(tranx).await;
+            }
+        };
+
+        Ok(Response::new(Box::pin(outcomes)))
+    }
+
+    async fn get_account(
+        &self,
+        request: Request<pb::GetAccountRequest>,
+    ) -> Result<Response<pb::GetAccountReply>, Status> {
+        let client = ClientId(request.into_inner().client);
+
+        let balances: Vec<pb::Account> = account::client_keys(client)
+            .into_iter()
+            .map(|key| account_reply(account::get_account(key.0, key.1)))
+            .collect();
+
+        if balances.is_empty() {
+            return Err(Status::not_found(format!("unknown client {}", client)));
+        }
+
+        Ok(Response::new(pb::GetAccountReply { balances }))
+    }
+
+    async fn list_accounts(
+        &self,
+        _request: Request<pb::ListAccountsRequest>,
+    ) -> Result<Response<Self::ListAccountsStream>, Status> {
+        let keys = account::CLIENTS.lock().unwrap().to_vec();
+
+        let accounts = stream! {
+            for key in keys {
+                yield Ok(account_reply(account::get_account(key.0, key.1)));
+            }
+        };
+
+        Ok(Response::new(Box::pin(accounts)))
+    }
+
+    // Sends the current balances first, then every balance as it changes
+    async fn watch_accounts(
+        &self,
+        request: Request<pb::WatchAccountsRequest>,
+    ) -> Result<Response<Self::WatchAccountsStream>, Status> {
+        let client = request.into_inner().client.map(ClientId);
+        let watched = move |key: ClientId| client.is_none_or(|client| client == key);
+
+        // subscribe before reading the balances so no change falls in between
+        let mut events = Box::pin(events::subscribe());
+        let keys = account::CLIENTS.lock().unwrap().to_vec();
+
+        let changes = stream! {
+            for key in keys.into_iter().filter(|key| watched(key.0)) {
+                yield Ok(account_reply(account::get_account(key.0, key.1)));
+            }
+
+            while let Some(event) = events.next().await {
+                match event {
+                    Ok(event) if watched(event.client) => yield Ok(account_reply(event.new)),
+                    Ok(_) => {}
+                    // the watcher no longer knows every balance, so it has to watch again
+                    Err(Missed(missed)) => {
+                        yield Err(Status::data_loss(format!("missed {} account events", missed)));
+                        break;
+                    }
+                }
+            }
+        };
+
+        Ok(Response::new(Box::pin(changes)))
+    }
+}
+
+fn transaction(received: pb::Transaction) -> Result<Transaction, String> {
+    let currency = match received.currency.trim() {
+        "" => Currency::default(),
+        code => Currency::new(code)?,
+    };
+
+    let to_currency = match received.to_currency {
+        Some(code) => Some(Currency::new(&code)?),
+        None => None,
+    };
+
+    let timestamp = match received.timestamp {
+        Some(value) => Some(timestamp::parse(&value)?),
+        None => None,
+    };
+
+    Ok(Transaction {
+        r#type: received.r#type,
+        client: ClientId(received.client),
+        tx: TxId(received.tx),
+        amount: received.amount as f32,
+        to_client: received.to_client.map(ClientId),
+        currency,
+        to_currency,
+        timestamp,
+    })
+}
+
+// Waiting for the engine lock blocks, so it happens off the async workers
+async fn process(tranx: Transaction) -> Result<pb::Outcome, Status> {
+    task::spawn_blocking(move || {
+        let outcome = account::process_all(std::slice::from_ref(&tranx)).remove(0);
+
+        outcome_reply(&tranx, outcome)
+    })
+    .await
+    .map_err(|err| Status::internal(err.to_string()))
+}
+
+fn outcome_reply(tranx: &Transaction, outcome: Outcome) -> pb::Outcome {
+    pb::Outcome {
+        client: tranx.client.0,
+        tx: tranx.tx.0,
+        applied: outcome.is_applied(),
+        pending: outcome.is_pending(),
+        reason: outcome
+            .reason()
+            .map_or(String::new(), |reason| reason.as_str().to_string()),
+    }
+}
+
+fn account_reply(acct: Account) -> pb::Account {
+    pb::Account {
+        client: acct.client.0,
+        currency: acct.currency.to_string(),
+        available: acct.available as f64,
+        held: acct.held as f64,
+        total: acct.total as f64,
+        locked: acct.locked,
+        closed: acct.closed,
+    }
+}
@@ -0,0 +1,156 @@
+use axum::body::Body;
+use axum::extract::{Path, Query};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures_util::stream;
+use serde::{Deserialize, Serialize};
+use std::io;
+use tokio::net::TcpListener;
+use tokio::task::{self, JoinError};
+
+use crate::ac::account::{self, Account};
+use crate::ac::disputes;
+use crate::ac::outcome::Reject;
+use crate::tx::id::{ClientId, TxId};
+use crate::tx::transaction::{self, Dispute, Transaction};
+
+/// The address the service binds to unless told otherwise.
+pub const DEFAULT_BIND: &str = "127.0.0.1:8080";
+
+/// `POST /transactions` takes either one transaction or an array of them.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Submission {
+    /// A single transaction.
+    One(Transaction),
+    /// Transactions processed in order.
+    Batch(Vec<Transaction>),
+}
+
+/// The outcome of one submitted transaction.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Processed {
+    /// The client of the submitted transaction.
+    pub client: ClientId,
+    /// The tx id of the submitted transaction.
+    pub tx: TxId,
+    /// Whether the balances were changed.
+    pub applied: bool,
+    /// Held for review, released later by `approve` or `decline`.
+    pub pending: bool,
+    /// Why the transaction was rejected, if it was.
+    pub reason: Option<Reject>,
+}
+
+/// Query of `GET /disputes`.
+#[derive(Debug, Deserialize)]
+pub struct DisputeFilter {
+    /// Only list disputes that still hold funds.
+    #[serde(default)]
+    pub open: bool,
+}
+
+/// The routes of the API.
+pub fn router() -> Router {
+    Router::new()
+        .route("/transactions", post(submit))
+        .route("/accounts", get(accounts))
+        .route("/accounts/{client}", get(client_account))
+        .route("/disputes", get(list_disputes))
+}
+
+/// Serves the API until ctrl-c is received.
+pub async fn serve(listener: TcpListener) -> io::Result<()> {
+    axum::serve(listener, router())
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+        .await
+}
+
+async fn submit(Json(submission): Json<Submission>) -> Response {
+    let (batch, one) = match submission {
+        Submission::One(tranx) => (vec![tranx], true),
+        Submission::Batch(batch) => (batch, false),
+    };
+
+    match process(batch).await {
+        Ok(mut processed) if one => Json(processed.remove(0)).into_response(),
+        Ok(processed) => Json(processed).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+    }
+}
+
+// Requests are served concurrently but batches are processed one at a time.
+// Waiting for the engine lock blocks, so it happens off the async workers.
+async fn process(batch: Vec<Transaction>) -> Result<Vec<Processed>, JoinError> {
+    task::spawn_blocking(move || {
+        let outcomes = account::process_all(&batch);
+
+        batch
+            .iter()
+            .zip(outcomes)
+            .map(|(tranx, outcome)| Processed {
+                client: tranx.client,
+                tx: tranx.tx,
+                applied: outcome.is_applied(),
+                pending: outcome.is_pending(),
+                reason: outcome.reason(),
+            })
+            .collect()
+    })
+    .await
+}
+
+// Every currency bucket of one client
+async fn client_account(Path(client): Path<u64>) -> Response {
+    let accounts: Vec<Account> = account::client_keys(ClientId(client))
+        .into_iter()
+        .map(|key| account::get_account(key.0, key.1))
+        .collect();
+
+    if accounts.is_empty() {
+        return (StatusCode::NOT_FOUND, format!("unknown client {}", client)).into_response();
+    }
+
+    Json(accounts).into_response()
+}
+
+// Streams the same CSV as the batch export, one row per chunk
+async fn accounts() -> Response {
+    let keys = account::CLIENTS.lock().unwrap().to_vec();
+
+    let columns = stream::iter(vec![Ok::<_, io::Error>(
+        "client,currency,available,held,total,locked,closed,updated\n".to_string(),
+    )]);
+    let rows = stream::iter(keys.into_iter().map(|key| {
+        let acct = account::get_account(key.0, key.1);
+
+        let mut csv_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(vec![]);
+        csv_writer.serialize(acct).map_err(io::Error::other)?;
+        let row = csv_writer.into_inner().map_err(io::Error::other)?;
+
+        String::from_utf8(row).map_err(io::Error::other)
+    }));
+
+    let body = Body::from_stream(futures_util::StreamExt::chain(columns, rows));
+
+    ([(header::CONTENT_TYPE, "text/csv")], body).into_response()
+}
+
+async fn list_disputes(Query(filter): Query<DisputeFilter>) -> Response {
+    if filter.open {
+        return Json(disputes::open()).into_response();
+    }
+
+    let all: Vec<Dispute> = transaction::DISPUTES
+        .lock()
+        .unwrap()
+        .reads(|disputes| disputes.map(|(_, dis)| *dis).collect());
+
+    Json(all).into_response()
+}
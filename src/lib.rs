@@ -0,0 +1,55 @@
+//! Transaction processing engine.
+//!
+//! The engine reads client transactions (deposits, withdrawals, transfers,
+//! conversions, disputes and admin actions), applies them to per-client,
+//! per-currency balances and keeps a journal and a double-entry ledger of
+//! everything it did.
+//!
+//! State is held in process-wide stores, so one process runs one engine.
+//! Feed transactions through [`process`] in order and read balances back with
+//! [`get_account`]:
+//!
+//! ```
+//! use transaction_processor::{get_account, process, ClientId, Currency, Outcome, Transaction, TxId};
+//!
+//! let deposit = Transaction::new("deposit".to_string(), ClientId(1), TxId(1), 10.0);
+//! assert_eq!(process(&deposit), Outcome::Applied);
+//!
+//! let acct = get_account(ClientId(1), Currency::default());
+//! assert_eq!(acct.available, 10.0);
+//! ```
+//!
+//! Rejected transactions are not errors: [`process`] returns an [`Outcome`]
+//! carrying the [`Reject`] reason, and the same outcome is journaled.
+//! Only the functions that load input files or write reports can fail, and
+//! they return an [`Error`].
+//!
+//! Every balance a transaction changes is published as an [`AccountChanged`]
+//! event, which [`ac::events::subscribe`] streams to any number of subscribers.
+
+#![warn(missing_docs)]
+
+/// Accounts, the processing engine and the reports built on it.
+pub mod ac;
+/// The error returned when an input file or a report cannot be handled.
+pub mod error;
+/// The gRPC service defined in `proto/processor.proto`.
+pub mod grpc;
+/// The HTTP API for running the engine as a long-lived service.
+pub mod http;
+/// The generic key-value store backing the engine state.
+pub mod storage;
+/// The line-delimited TCP ingest listener.
+pub mod tcp;
+/// Transactions and their inputs: ids, currencies, rates, batches and the journal.
+pub mod tx;
+
+pub use ac::account::{get_account, process, Account, AccountKey};
+pub use ac::events::AccountChanged;
+pub use ac::invariant::Violation;
+pub use ac::outcome::{Outcome, Reject};
+pub use error::Error;
+pub use storage::{Storage, StoreKey};
+pub use tx::currency::Currency;
+pub use tx::id::{ClientId, TxId};
+pub use tx::transaction::{Dispute, Transaction};
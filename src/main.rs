@@ -1,18 +1,26 @@
-extern crate csv;
-
-mod ac;
-pub mod storage;
-mod tx;
 use async_stream::stream;
 use futures_util::{pin_mut, StreamExt};
 
-use crate::ac::account;
-use crate::tx::transaction::Transaction;
 use std::env;
 use std::error::Error;
 use std::ffi::OsString;
 use std::fs::File;
+use std::path::Path;
 use std::process;
+use std::slice;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tokio::task;
+use transaction_processor::ac::authorization::{self, Clock};
+use transaction_processor::ac::{
+    account, disputes, events, fee, invariant, ledger, limits, review, rules, statement, summary,
+};
+use transaction_processor::tx::batch::{self, Admission, Batch};
+use transaction_processor::tx::journal;
+use transaction_processor::tx::rates::{self, Rounding};
+use transaction_processor::tx::spool::Spool;
+use transaction_processor::{grpc, http, tcp};
+use transaction_processor::{ClientId, Outcome, Reject, Transaction};
 
 #[tokio::main]
 async fn main() {
@@ -22,40 +30,580 @@ async fn main() {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Command {
+    // print the account summary, the default
+    Export,
+    // verify the ledger instead of exporting accounts
+    Check,
+    // print or write client statements
+    Statement,
+    // serve the HTTP API until stopped
+    Serve,
+    // ingest transactions from TCP connections until stopped
+    Listen,
+    // ingest files dropped into a spool directory until stopped
+    Watch,
+    // serve the gRPC API until stopped
+    Grpc,
+}
+
+struct Options {
+    command: Command,
+    // optional for `serve`, `listen` and `grpc`, where it is ingested first
+    file_path: Option<OsString>,
+    bind: Option<String>,
+    queue: usize,
+    poll_ms: u64,
+    journal: Option<OsString>,
+    events: Option<OsString>,
+    fees: Option<OsString>,
+    limits: Option<OsString>,
+    rules: Option<OsString>,
+    auth_expiry: Option<String>,
+    rates: Option<OsString>,
+    rounding: Rounding,
+    batch_id: Option<String>,
+    batches: Option<OsString>,
+    skipped: Option<OsString>,
+    trial_balance: Option<OsString>,
+    open_disputes: Option<OsString>,
+    review_queue: Option<OsString>,
+    summary: bool,
+    summary_json: Option<OsString>,
+    strict: bool,
+    client: Option<ClientId>,
+    out_dir: Option<OsString>,
+    check_each: bool,
+    flag_out_of_order: bool,
+}
+
 async fn parse_csv_file() -> Result<(), Box<dyn Error>> {
-    let file_path = read_arg()?;
+    let started = Instant::now();
+    let options = read_args()?;
+
+    journal::flag_out_of_order(options.flag_out_of_order);
+
+    if let Some(path) = &options.events {
+        events::write_to(path)?;
+    }
+
+    if let Some(path) = &options.fees {
+        fee::load(path)?;
+    }
+
+    if let Some(path) = &options.limits {
+        limits::load(path)?;
+    }
+
+    if let Some(path) = &options.rules {
+        rules::load(path)?;
+    }
+
+    if let Some(expiry) = &options.auth_expiry {
+        authorization::set_expiry(expiry)?;
+    }
+
+    // clients of the services may leave timestamps out, so holds there run
+    // out by the wall clock, including those of a file ingested first
+    if options.is_service() {
+        authorization::set_clock(Clock::Wall);
+    }
+
+    if let Some(path) = &options.rates {
+        rates::load(path)?;
+    }
+    rates::set_rounding(options.rounding);
+
+    if let Some(path) = &options.batches {
+        batch::load(path)?;
+    }
+
+    let mut rows_read = match &options.file_path {
+        // in watch mode the path is the spool directory
+        Some(dir) if options.command == Command::Watch => watch(&options, dir).await?,
+        Some(file_path) => match ingest(&options, file_path).await {
+            Ok(rows) => rows,
+            Err(err) => {
+                // keep how far the file got, so running it again resumes after the failure
+                if let Some(path) = &options.batches {
+                    batch::save(path)?;
+                }
+                return Err(err);
+            }
+        },
+        None => 0,
+    };
+
+    // holds that ran out by the end of the input are released before anything is reported
+    authorization::sweep();
+
+    match options.command {
+        Command::Export => account::export(),
+        Command::Check => {
+            let checked = invariant::check_all().map_err(|violation| violation.to_string())?;
+            println!("ledger ok: {} accounts checked", checked);
+        }
+        Command::Statement => match (options.client, &options.out_dir) {
+            (Some(client), _) => statement::print(client)?,
+            (None, Some(dir)) => {
+                let written = statement::export_all(dir)?;
+                eprintln!("wrote {} statements to {}", written, dir.to_string_lossy());
+            }
+            (None, None) => {
+                return Err(From::from("expected --client or --out-dir with statement"))
+            }
+        },
+        Command::Serve => {
+            let bind = options.bind.as_deref().unwrap_or(http::DEFAULT_BIND);
+            let listener = TcpListener::bind(bind).await?;
+            eprintln!("listening on {}", listener.local_addr()?);
+            http::serve(listener).await?;
+        }
+        Command::Watch => {}
+        Command::Grpc => {
+            let bind = options.bind.as_deref().unwrap_or(grpc::DEFAULT_BIND);
+            let listener = TcpListener::bind(bind).await?;
+            eprintln!("listening on {}", listener.local_addr()?);
+            grpc::serve(listener).await?;
+        }
+        Command::Listen => {
+            let bind = options.bind.as_deref().unwrap_or(tcp::DEFAULT_BIND);
+            let listener = TcpListener::bind(bind).await?;
+            eprintln!("listening on {}", listener.local_addr()?);
+            rows_read += listen(&options, listener).await?;
+        }
+    }
+
+    // a service ran until stopped, so holds may have run out since the sweep above
+    if options.is_service() {
+        authorization::sweep();
+    }
+
+    if options.events.is_some() {
+        events::close()?;
+    }
+
+    for entry in journal::out_of_order() {
+        eprintln!(
+            "out-of-order timestamp for client {} on tx {}",
+            entry.client, entry.tx
+        );
+    }
+
+    if let Some(path) = options.journal {
+        journal::export(&path)?;
+    }
+
+    if let Some(path) = options.batches {
+        batch::save(&path)?;
+    }
+
+    if let Some(path) = options.skipped {
+        batch::export_skipped(&path)?;
+    }
+
+    if let Some(path) = options.trial_balance {
+        ledger::export_trial_balance(&path)?;
+    }
+
+    if let Some(path) = options.open_disputes {
+        disputes::export(&path)?;
+    }
+
+    if let Some(path) = options.review_queue {
+        let pending = review::export_pending(&path)?;
+        eprintln!("{} transactions waiting for review", pending);
+    }
+
+    if options.summary || options.summary_json.is_some() {
+        let summary = summary::build(rows_read, started.elapsed());
+
+        if options.summary {
+            summary::print(&summary);
+        }
+
+        if let Some(path) = options.summary_json {
+            summary::export_json(&summary, &path)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Processes one transaction, failing the run where `--strict` or `--check` ask
+// for it. This blocks on the engine lock and can run on a blocking thread.
+fn apply(strict: bool, check_each: bool, record: &Transaction) -> Result<Outcome, String> {
+    let outcome = account::process_all(slice::from_ref(record)).remove(0);
+
+    if let Some(reason) = outcome.reason().filter(Reject::is_limit) {
+        eprintln!(
+            "withdrawal limit hit for client {} on tx {}: {}",
+            record.client,
+            record.tx,
+            reason.as_str()
+        );
+    }
+
+    // in strict mode a reused transaction id with a different payload fails the run
+    if strict && outcome == Outcome::Rejected(Reject::ConflictingTx) {
+        return Err(format!(
+            "conflicting duplicate transaction id {} for client {}",
+            record.tx, record.client
+        ));
+    }
+
+    if check_each {
+        invariant::check_after(record).map_err(|violation| violation.to_string())?;
+    }
+
+    Ok(outcome)
+}
+
+// Feeds transactions received over TCP through the engine until ctrl-c,
+// returning the number of lines processed
+async fn listen(options: &Options, listener: TcpListener) -> Result<usize, Box<dyn Error>> {
+    let tx_stream = tcp::listen(listener, options.queue);
+
+    pin_mut!(tx_stream);
+
+    let mut rows_read = 0;
+    loop {
+        let submitted = tokio::select! {
+            submitted = tx_stream.next() => submitted,
+            _ = tokio::signal::ctrl_c() => None,
+        };
+
+        let submitted = match submitted {
+            Some(Ok(submitted)) => submitted,
+            Some(Err(err)) => {
+                eprintln!("failed to accept connection: {}", err);
+                continue;
+            }
+            None => break,
+        };
+
+        rows_read += 1;
+
+        // lines are applied off the async workers, as the HTTP and gRPC services do
+        let (strict, check_each) = (options.strict, options.check_each);
+        let tranx = submitted.tranx.clone();
+        let outcome = task::spawn_blocking(move || apply(strict, check_each, &tranx)).await??;
+        submitted.ack(outcome);
+    }
+
+    Ok(rows_read)
+}
+
+// Ingests files dropped into a spool directory, in name order, until ctrl-c,
+// returning the number of rows read
+async fn watch(options: &Options, dir: &OsString) -> Result<usize, Box<dyn Error>> {
+    let mut spool = Spool::new(Path::new(dir))?;
+    let mut poll = tokio::time::interval(Duration::from_millis(options.poll_ms));
+
+    let mut rows_read = 0;
+    loop {
+        tokio::select! {
+            _ = poll.tick() => {}
+            _ = tokio::signal::ctrl_c() => break,
+        }
+
+        for path in spool.ready()? {
+            match ingest(options, &path.clone().into_os_string()).await {
+                Ok(rows) => {
+                    rows_read += rows;
+                    spool.done(&path)?;
+                }
+                Err(err) => {
+                    eprintln!("failed to ingest {}: {}", path.display(), err);
+                    spool.failed(&path, &err.to_string())?;
+                }
+            }
+        }
+    }
+
+    Ok(rows_read)
+}
+
+// Streams one input file through the engine unless it was already ingested,
+// returning the number of rows read
+async fn ingest(options: &Options, file_path: &OsString) -> Result<usize, Box<dyn Error>> {
+    let hash = batch::fingerprint(file_path)?;
+    let id = options
+        .batch_id
+        .clone()
+        .unwrap_or_else(|| batch::default_id(file_path));
+
+    let (batch, resume_at) = match batch::admit(&id, &hash)? {
+        Admission::New(batch) => (batch, 0),
+        Admission::Resume(batch) => {
+            let resume_at = batch.stopped_at.unwrap_or_default();
+            eprintln!("resuming batch {} after row {}", id, resume_at);
+            (batch, resume_at)
+        }
+        Admission::Duplicate(batch) => {
+            eprintln!(
+                "skipping batch {}: already ingested as {} at {}",
+                id, batch.id, batch.ingested_at
+            );
+            batch::skip(&id, &batch);
+            return Ok(0);
+        }
+    };
+
+    // a malformed row fails the file before any of its rows are applied
+    validate(
+        file_path,
+        options.auth_expiry.is_some() && !options.is_service(),
+    )?;
+
     let file = File::open(file_path)?;
 
     let tx_stream = stream! {
         let mut rdr = csv::ReaderBuilder::new()
             .trim(csv::Trim::All)
+            .flexible(true)
             .from_reader(file);
 
-        for result in rdr.deserialize() {
-            let record: Transaction = result.expect("invalid record in csv file. please check your input");
+        for result in rdr.deserialize().skip(resume_at) {
+            let record: Result<Transaction, csv::Error> = result;
             yield record;
         }
     };
 
     pin_mut!(tx_stream);
 
+    let mut rows_read = 0;
     while let Some(record) = tx_stream.next().await {
         // println!("{:?}", record);
-        account::process_deposit(&record);
-        account::process_withdrawal(&record);
-        account::process_dispute(&record);
-        account::process_resolve(&record);
-        account::process_chargeback(&record);
+        let record = record.map_err(|err| format!("invalid record in csv file: {}", err))?;
+        rows_read += 1;
+
+        // the rows before this one stay applied, so a second attempt picks up after it
+        if let Err(err) = apply(options.strict, options.check_each, &record) {
+            batch::register(Batch {
+                stopped_at: Some(resume_at + rows_read),
+                ..batch
+            });
+            return Err(err.into());
+        }
     }
 
-    account::export();
+    batch::register(Batch {
+        stopped_at: None,
+        ..batch
+    });
+
+    Ok(rows_read)
+}
+
+// Reads every row of an input file without applying any of them. Holds
+// expire by row time in files, so with an expiry every row needs a timestamp.
+fn validate(file_path: &OsString, timed: bool) -> Result<(), Box<dyn Error>> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_path(file_path)?;
+
+    for (row, result) in rdr.deserialize::<Transaction>().enumerate() {
+        let record = result.map_err(|err| format!("invalid record in csv file: {}", err))?;
+
+        if timed && record.timestamp.is_none() {
+            return Err(From::from(format!(
+                "--auth-expiry needs a timestamp on every row; row {} has none",
+                row + 1
+            )));
+        }
+    }
 
     Ok(())
 }
 
-fn read_arg() -> Result<OsString, Box<dyn Error>> {
-    match env::args_os().nth(1) {
-        None => Err(From::from("expected a csv file argument")),
-        Some(file_path) => Ok(file_path),
+impl Options {
+    // commands that keep taking transactions until stopped
+    fn is_service(&self) -> bool {
+        [Command::Serve, Command::Listen, Command::Grpc].contains(&self.command)
+    }
+}
+
+fn read_args() -> Result<Options, Box<dyn Error>> {
+    let mut file_path: Option<OsString> = None;
+    let mut journal: Option<OsString> = None;
+    let mut events: Option<OsString> = None;
+    let mut fees: Option<OsString> = None;
+    let mut limits: Option<OsString> = None;
+    let mut rules: Option<OsString> = None;
+    let mut auth_expiry: Option<String> = None;
+    let mut rates: Option<OsString> = None;
+    let mut rounding = Rounding::Down;
+    let mut batch_id: Option<String> = None;
+    let mut batches: Option<OsString> = None;
+    let mut skipped: Option<OsString> = None;
+    let mut trial_balance: Option<OsString> = None;
+    let mut open_disputes: Option<OsString> = None;
+    let mut review_queue: Option<OsString> = None;
+    let mut summary = false;
+    let mut summary_json: Option<OsString> = None;
+    let mut strict = false;
+    let mut command = Command::Export;
+    let mut bind: Option<String> = None;
+    let mut queue = tcp::DEFAULT_QUEUE;
+    let mut poll_ms = 1000;
+    let mut client: Option<ClientId> = None;
+    let mut out_dir: Option<OsString> = None;
+    let mut check_each = false;
+    let mut flag_out_of_order = false;
+
+    let mut args = env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--journal") => match args.next() {
+                None => return Err(From::from("expected a path after --journal")),
+                Some(path) => journal = Some(path),
+            },
+            Some("--events") => match args.next() {
+                None => return Err(From::from("expected a path after --events")),
+                Some(path) => events = Some(path),
+            },
+            Some("--fees") => match args.next() {
+                None => return Err(From::from("expected a path after --fees")),
+                Some(path) => fees = Some(path),
+            },
+            Some("--limits") => match args.next() {
+                None => return Err(From::from("expected a path after --limits")),
+                Some(path) => limits = Some(path),
+            },
+            Some("--rules") => match args.next() {
+                None => return Err(From::from("expected a path after --rules")),
+                Some(path) => rules = Some(path),
+            },
+            Some("--auth-expiry") => match args.next().as_ref().and_then(|span| span.to_str()) {
+                None => return Err(From::from("expected a duration after --auth-expiry")),
+                Some(span) => auth_expiry = Some(span.to_string()),
+            },
+            Some("--rates") => match args.next() {
+                None => return Err(From::from("expected a path after --rates")),
+                Some(path) => rates = Some(path),
+            },
+            Some("--rounding") => match args.next().as_ref().and_then(|mode| mode.to_str()) {
+                None => return Err(From::from("expected down, nearest or up after --rounding")),
+                Some(mode) => rounding = mode.parse()?,
+            },
+            Some("--batch-id") => match args.next().as_ref().and_then(|id| id.to_str()) {
+                None => return Err(From::from("expected an id after --batch-id")),
+                Some(id) => batch_id = Some(id.to_string()),
+            },
+            Some("--batches") => match args.next() {
+                None => return Err(From::from("expected a path after --batches")),
+                Some(path) => batches = Some(path),
+            },
+            Some("--skipped") => match args.next() {
+                None => return Err(From::from("expected a path after --skipped")),
+                Some(path) => skipped = Some(path),
+            },
+            Some("--trial-balance") => match args.next() {
+                None => return Err(From::from("expected a path after --trial-balance")),
+                Some(path) => trial_balance = Some(path),
+            },
+            Some("--open-disputes") => match args.next() {
+                None => return Err(From::from("expected a path after --open-disputes")),
+                Some(path) => open_disputes = Some(path),
+            },
+            Some("--review-queue") => match args.next() {
+                None => return Err(From::from("expected a path after --review-queue")),
+                Some(path) => review_queue = Some(path),
+            },
+            Some("--summary") => summary = true,
+            Some("--summary-json") => match args.next() {
+                None => return Err(From::from("expected a path after --summary-json")),
+                Some(path) => summary_json = Some(path),
+            },
+            Some("--strict") => strict = true,
+            Some("--check") => check_each = true,
+            Some("--client") => match args.next().as_ref().and_then(|id| id.to_str()) {
+                None => return Err(From::from("expected an id after --client")),
+                Some(id) => client = Some(ClientId(id.parse()?)),
+            },
+            Some("--out-dir") => match args.next() {
+                None => return Err(From::from("expected a path after --out-dir")),
+                Some(path) => out_dir = Some(path),
+            },
+            Some("check") if file_path.is_none() && command == Command::Export => {
+                command = Command::Check
+            }
+            Some("statement") if file_path.is_none() && command == Command::Export => {
+                command = Command::Statement
+            }
+            Some("serve") if file_path.is_none() && command == Command::Export => {
+                command = Command::Serve
+            }
+            Some("listen") if file_path.is_none() && command == Command::Export => {
+                command = Command::Listen
+            }
+            Some("watch") if file_path.is_none() && command == Command::Export => {
+                command = Command::Watch
+            }
+            Some("grpc") if file_path.is_none() && command == Command::Export => {
+                command = Command::Grpc
+            }
+            Some("--bind") => match args.next().as_ref().and_then(|addr| addr.to_str()) {
+                None => return Err(From::from("expected an address after --bind")),
+                Some(addr) => bind = Some(addr.to_string()),
+            },
+            Some("--poll-ms") => match args.next().as_ref().and_then(|ms| ms.to_str()) {
+                None => return Err(From::from("expected milliseconds after --poll-ms")),
+                Some(ms) => poll_ms = ms.parse()?,
+            },
+            Some("--queue") => match args.next().as_ref().and_then(|size| size.to_str()) {
+                None => return Err(From::from("expected a size after --queue")),
+                Some(size) => match size.parse()? {
+                    0 => return Err(From::from("the queue needs room for at least one line")),
+                    size => queue = size,
+                },
+            },
+            Some("--flag-out-of-order") => flag_out_of_order = true,
+            _ => file_path = Some(arg),
+        }
+    }
+
+    // one id for every file in the spool would make each file after the first a conflict
+    if command == Command::Watch && batch_id.is_some() {
+        return Err(From::from(
+            "--batch-id cannot be used with watch; each file is named after itself",
+        ));
+    }
+
+    match file_path {
+        None if ![Command::Serve, Command::Listen, Command::Grpc].contains(&command) => {
+            Err(From::from("expected a csv file argument"))
+        }
+        file_path => Ok(Options {
+            command,
+            file_path,
+            bind,
+            queue,
+            poll_ms,
+            journal,
+            events,
+            fees,
+            limits,
+            rules,
+            auth_expiry,
+            rates,
+            rounding,
+            batch_id,
+            batches,
+            skipped,
+            trial_balance,
+            open_disputes,
+            review_queue,
+            summary,
+            summary_json,
+            strict,
+            client,
+            out_dir,
+            check_each,
+            flag_out_of_order,
+        }),
     }
 }
@@ -4,23 +4,29 @@ use std::{
     sync::{Arc, Mutex},
 };
 
+/// Gives an item the key it is stored under.
 pub trait StoreKey {
+    /// The type items are looked up by.
     type Key: Hash + Eq;
 
+    /// The key the item is stored under.
     fn key(&self) -> Self::Key;
 }
 
+/// A thread-safe map of items keyed by [`StoreKey`].
 pub struct Storage<K, D> {
     data: Arc<Mutex<HashMap<K, D>>>,
 }
 
 impl<K: Hash + Eq, D: StoreKey<Key = K>> Storage<K, D> {
+    /// An empty store.
     pub fn new() -> Self {
         Self {
             data: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
+    /// Runs `f` on the item stored under `id`, if any.
     pub fn read<F, R>(&self, id: K, f: F) -> R
     where
         F: FnOnce(Option<&D>) -> R,
@@ -28,6 +34,7 @@ impl<K: Hash + Eq, D: StoreKey<Key = K>> Storage<K, D> {
         f(self.data.lock().unwrap().get(&id))
     }
 
+    /// Runs `f` on every stored item.
     pub fn reads<F, R>(&self, f: F) -> R
     where
         F: FnOnce(Iter<K, D>) -> R,
@@ -35,6 +42,7 @@ impl<K: Hash + Eq, D: StoreKey<Key = K>> Storage<K, D> {
         f(self.data.lock().unwrap().iter())
     }
 
+    /// Stores `item` under its key and returns whether it replaced one.
     pub fn insert(&self, item: D) -> bool {
         let acc = self.data.lock().unwrap().insert(item.key(), item);
 
@@ -45,6 +53,7 @@ impl<K: Hash + Eq, D: StoreKey<Key = K>> Storage<K, D> {
         false
     }
 
+    /// Runs `f` on the item stored under `id` with write access.
     pub fn modify<F, R>(&self, id: K, f: F) -> R
     where
         F: FnOnce(Option<&mut D>) -> R,
@@ -52,13 +61,26 @@ impl<K: Hash + Eq, D: StoreKey<Key = K>> Storage<K, D> {
         f(self.data.lock().unwrap().get_mut(&id))
     }
 
+    /// Removes the item stored under `id` and returns it, if any.
+    pub fn remove(&self, id: K) -> Option<D> {
+        self.data.lock().unwrap().remove(&id)
+    }
+
+    /// Whether an item is stored under `id`.
     pub fn exists(&self, id: K) -> bool {
         self.data.lock().unwrap().contains_key(&id)
     }
 }
 
+impl<K: Hash + Eq, D: StoreKey<Key = K>> Default for Storage<K, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // Tests
 #[cfg(test)]
+#[allow(clippy::clone_on_copy, clippy::needless_return)]
 mod tests {
     use crate::storage::Storage;
 
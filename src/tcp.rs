@@ -0,0 +1,217 @@
+use async_stream::stream;
+use futures_util::Stream;
+use std::io;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::ac::outcome::Outcome;
+use crate::tx::id::TxId;
+use crate::tx::transaction::Transaction;
+
+/// The address the listener binds to unless told otherwise.
+pub const DEFAULT_BIND: &str = "127.0.0.1:9090";
+
+/// How many lines may wait for processing before connections stop being read.
+pub const DEFAULT_QUEUE: usize = 1024;
+
+// columns assumed for csv lines until a connection sends its own header
+const DEFAULT_HEADER: [&str; 4] = ["type", "client", "tx", "amount"];
+
+/// A transaction received on a connection, waiting for its outcome.
+#[derive(Debug)]
+pub struct Submitted {
+    /// The transaction parsed from the line.
+    pub tranx: Transaction,
+    reply: oneshot::Sender<Outcome>,
+}
+
+impl Submitted {
+    /// Sends the outcome back to the connection that submitted the transaction.
+    pub fn ack(self, outcome: Outcome) {
+        // the connection may have gone away in the meantime
+        let _ = self.reply.send(outcome);
+    }
+}
+
+/// Accepts connections and yields their transactions in the order they were queued.
+/// Once `queue` lines are waiting, connections are no longer read until the
+/// consumer catches up, which pushes back on the producers through TCP.
+/// A connection that fails to be accepted is yielded as an error, and the
+/// listener keeps accepting.
+pub fn listen(listener: TcpListener, queue: usize) -> impl Stream<Item = io::Result<Submitted>> {
+    let (sender, mut receiver) = mpsc::channel(queue);
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((socket, _)) => {
+                    tokio::spawn(handle(socket, sender.clone(), queue));
+                }
+                Err(err) => {
+                    if sender.send(Err(err)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    stream! {
+        while let Some(submitted) = receiver.recv().await {
+            yield submitted;
+        }
+    }
+}
+
+// A response line, either known right away or waiting for the outcome of its transaction
+enum Response {
+    Error(String),
+    Outcome(TxId, oneshot::Receiver<Outcome>),
+}
+
+// Reads one connection line by line and writes back one response per line:
+// `ack <tx>`, `reject <tx> <reason>`, `pending <tx>` or `error <message>`.
+// Lines keep being read while earlier ones wait for their outcome, and at most
+// `queue` responses of a connection are outstanding at a time.
+async fn handle(
+    socket: TcpStream,
+    sender: mpsc::Sender<io::Result<Submitted>>,
+    queue: usize,
+) -> io::Result<()> {
+    let (reader, writer) = socket.into_split();
+    let (responses, outstanding) = mpsc::channel(queue);
+
+    let replies = tokio::spawn(reply(writer, outstanding));
+    read(reader, sender, responses).await?;
+
+    replies.await.map_err(io::Error::other)?
+}
+
+async fn read(
+    reader: OwnedReadHalf,
+    sender: mpsc::Sender<io::Result<Submitted>>,
+    responses: mpsc::Sender<Response>,
+) -> io::Result<()> {
+    let mut lines = BufReader::new(reader).lines();
+    let mut header = csv::StringRecord::from(DEFAULT_HEADER.to_vec());
+
+    while let Some(line) = lines.next_line().await? {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let response = if line.starts_with("type,") {
+            // a csv header changes the columns of the lines that follow
+            match parse_record(line) {
+                Ok(record) => {
+                    header = record;
+                    continue;
+                }
+                Err(err) => Response::Error(err.to_string()),
+            }
+        } else {
+            match parse_line(line, &header) {
+                Ok(tranx) => {
+                    let tx = tranx.tx;
+                    let (reply, outcome) = oneshot::channel();
+
+                    // waits while the queue is full
+                    if sender.send(Ok(Submitted { tranx, reply })).await.is_err() {
+                        break;
+                    }
+
+                    Response::Outcome(tx, outcome)
+                }
+                Err(err) => Response::Error(err),
+            }
+        };
+
+        // the connection stopped taking responses
+        if responses.send(response).await.is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+// Writes the responses of a connection in the order its lines came in
+async fn reply(
+    mut writer: OwnedWriteHalf,
+    mut outstanding: mpsc::Receiver<Response>,
+) -> io::Result<()> {
+    while let Some(response) = outstanding.recv().await {
+        let response = match response {
+            Response::Error(err) => format!("error {}\n", err),
+            Response::Outcome(tx, outcome) => match outcome.await {
+                Ok(Outcome::Applied) => format!("ack {}\n", tx),
+                Ok(Outcome::Rejected(reason)) => format!("reject {} {}\n", tx, reason.as_str()),
+                Ok(Outcome::Pending) => format!("pending {}\n", tx),
+                Err(_) => break,
+            },
+        };
+        writer.write_all(response.as_bytes()).await?;
+    }
+
+    Ok(())
+}
+
+/// A line is either a JSON object or a CSV row.
+pub fn parse_line(line: &str, header: &csv::StringRecord) -> Result<Transaction, String> {
+    if line.starts_with('{') {
+        return serde_json::from_str(line).map_err(|err| err.to_string());
+    }
+
+    let record = parse_record(line).map_err(|err| err.to_string())?;
+
+    record
+        .deserialize(Some(header))
+        .map_err(|err| err.to_string())
+}
+
+fn parse_record(line: &str) -> io::Result<csv::StringRecord> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(line.as_bytes());
+
+    match rdr.records().next() {
+        Some(record) => record.map_err(io::Error::other),
+        None => Ok(csv::StringRecord::new()),
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::{parse_line, DEFAULT_HEADER};
+    use crate::tx::id::{ClientId, TxId};
+
+    #[test]
+    fn test_parse_line() {
+        let header = csv::StringRecord::from(DEFAULT_HEADER.to_vec());
+
+        let csv = parse_line("deposit, 1, 2, 3.5", &header).unwrap();
+        let json = parse_line(
+            r#"{"type":"deposit","client":1,"tx":2,"amount":3.5}"#,
+            &header,
+        )
+        .unwrap();
+
+        assert!(
+            csv == json && csv.client == ClientId(1) && csv.tx == TxId(2),
+            "csv and json lines should parse alike; got {:?} and {:?}",
+            csv,
+            json
+        );
+
+        assert!(
+            parse_line("deposit,one,2,3.5", &header).is_err(),
+            "invalid client id should fail"
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::error::Error;
+use crate::storage::{Storage, StoreKey};
+
+/// Batches that have been ingested, keyed by content hash.
+pub static BATCHES: Lazy<Mutex<Storage<String, Batch>>> = Lazy::new(|| Mutex::new(Storage::new()));
+
+/// Batches that were recognised as already ingested during this run.
+pub static SKIPPED: Lazy<Mutex<Vec<Skipped>>> = Lazy::new(|| Mutex::new(vec![]));
+
+/// An ingested input file.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Batch {
+    /// The id the file was ingested as.
+    pub id: String,
+    /// SHA-256 of the file contents.
+    pub hash: String,
+    /// When the file was first ingested.
+    pub ingested_at: DateTime<Utc>,
+    /// Rows handed to the engine when ingesting stopped early, empty once complete.
+    #[serde(default)]
+    pub stopped_at: Option<usize>,
+}
+
+impl StoreKey for Batch {
+    type Key = String;
+
+    fn key(&self) -> Self::Key {
+        self.hash.to_string()
+    }
+}
+
+/// A file skipped as a replay of a batch already ingested.
+#[derive(Debug, Clone, Serialize)]
+pub struct Skipped {
+    /// The id the file was submitted as this time.
+    pub id: String,
+    /// SHA-256 of the file contents.
+    pub hash: String,
+    /// The id it was first ingested as.
+    pub ingested_as: String,
+    /// When it was first ingested.
+    pub ingested_at: DateTime<Utc>,
+}
+
+/// Whether a file is ingested, resumed or skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Admission {
+    /// Not seen before.
+    New(Batch),
+    /// Ingested before up to `stopped_at`, to be picked up after that row. For
+    /// corrected content this is the row before the one that stopped it.
+    Resume(Batch),
+    /// Fully ingested before.
+    Duplicate(Batch),
+}
+
+/// Hashes a file in chunks so large inputs are never loaded at once.
+pub fn fingerprint(path: &OsString) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 8192];
+
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Default batch id when the caller does not supply one.
+pub fn default_id(path: &OsString) -> String {
+    Path::new(path)
+        .file_name()
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Decides whether a batch is new or a replay of one already ingested.
+pub fn admit(id: &str, hash: &str) -> Result<Admission, Error> {
+    let by_hash: Option<Batch> = BATCHES
+        .lock()
+        .unwrap()
+        .read(hash.to_string(), |batch| batch.cloned());
+
+    if let Some(batch) = by_hash {
+        return match batch.stopped_at {
+            Some(_) => Ok(Admission::Resume(batch)),
+            None => Ok(Admission::Duplicate(batch)),
+        };
+    }
+
+    let by_id: Option<Batch> = BATCHES
+        .lock()
+        .unwrap()
+        .reads(|mut batches| batches.find(|(_, b)| b.id == id).map(|(_, b)| b.clone()));
+
+    if let Some(batch) = by_id {
+        // corrected content for a batch that stopped early picks up at the row
+        // that stopped it, taking the rows before it as unchanged
+        if let Some(stopped_at) = batch.stopped_at {
+            return Ok(Admission::Resume(Batch {
+                hash: hash.to_string(),
+                stopped_at: Some(stopped_at.saturating_sub(1)),
+                ..batch
+            }));
+        }
+
+        return Err(Error::Invalid(format!(
+            "batch {} was already ingested with different content ({}); new content needs a new batch id",
+            id, batch.hash
+        )));
+    }
+
+    Ok(Admission::New(Batch {
+        id: id.to_string(),
+        hash: hash.to_string(),
+        ingested_at: Utc::now(),
+        stopped_at: None,
+    }))
+}
+
+/// Records a batch as ingested, replacing the content it was ingested with before.
+pub fn register(batch: Batch) {
+    let batches = BATCHES.lock().unwrap();
+
+    let superseded: Vec<String> = batches.reads(|batches| {
+        batches
+            .filter(|(hash, b)| b.id == batch.id && **hash != batch.hash)
+            .map(|(hash, _)| hash.to_string())
+            .collect()
+    });
+    for hash in superseded {
+        batches.remove(hash);
+    }
+
+    batches.insert(batch);
+}
+
+/// Records a batch that is not ingested again.
+pub fn skip(id: &str, batch: &Batch) {
+    SKIPPED.lock().unwrap().push(Skipped {
+        id: id.to_string(),
+        hash: batch.hash.to_string(),
+        ingested_as: batch.id.to_string(),
+        ingested_at: batch.ingested_at,
+    });
+}
+
+/// Loads the registry of ingested batches, if one has been written before.
+pub fn load(path: &OsString) -> Result<(), Error> {
+    if !Path::new(path).exists() {
+        return Ok(());
+    }
+
+    let mut rdr = csv::Reader::from_path(path)?;
+    for result in rdr.deserialize() {
+        let batch: Batch = result?;
+        register(batch);
+    }
+
+    Ok(())
+}
+
+/// Writes the registry of ingested batches, oldest first.
+pub fn save(path: &OsString) -> Result<(), Error> {
+    let mut batches: Vec<Batch> = BATCHES
+        .lock()
+        .unwrap()
+        .reads(|batches| batches.map(|(_, b)| b.clone()).collect());
+    batches.sort_by_key(|b| b.ingested_at);
+
+    let mut csv_writer = csv::Writer::from_path(path)?;
+    for batch in batches {
+        csv_writer.serialize(batch)?;
+    }
+    csv_writer.flush()?;
+
+    Ok(())
+}
+
+/// Writes the batches skipped during this run to `path` as CSV.
+pub fn export_skipped(path: &OsString) -> Result<(), Error> {
+    let mut csv_writer = csv::Writer::from_path(path)?;
+
+    for skipped in SKIPPED.lock().unwrap().iter() {
+        csv_writer.serialize(skipped)?;
+    }
+    csv_writer.flush()?;
+
+    Ok(())
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::{admit, register, Admission, Batch, BATCHES};
+
+    #[test]
+    fn test_batch_admission() {
+        let first = match admit("batch-a", "hash-a").unwrap() {
+            Admission::New(batch) => batch,
+            other => panic!("expected a new batch, got {:?}", other),
+        };
+        register(first.clone());
+
+        // the same content under another name is still a replay
+        let replay = admit("batch-a-copy", "hash-a").unwrap();
+        assert!(
+            replay == Admission::Duplicate(first),
+            "replayed content not recognised; got {:?}",
+            replay
+        );
+
+        assert!(
+            admit("batch-a", "hash-b").is_err(),
+            "reused batch id with new content should fail"
+        );
+    }
+
+    #[test]
+    fn test_batch_resume() {
+        let batch = match admit("batch-c", "hash-c").unwrap() {
+            Admission::New(batch) => batch,
+            other => panic!("expected a new batch, got {:?}", other),
+        };
+        let stopped = Batch {
+            stopped_at: Some(2),
+            ..batch
+        };
+        register(stopped.clone());
+
+        // a batch that stopped early is picked up again instead of skipped
+        let retry = admit("batch-c", "hash-c").unwrap();
+        assert!(
+            retry == Admission::Resume(stopped.clone()),
+            "stopped batch should resume; expected {:?}, got {:?}",
+            stopped,
+            retry
+        );
+
+        let done = Batch {
+            stopped_at: None,
+            ..stopped
+        };
+        register(done.clone());
+
+        let replay = admit("batch-c", "hash-c").unwrap();
+        assert!(
+            replay == Admission::Duplicate(done),
+            "finished batch should be skipped; got {:?}",
+            replay
+        );
+    }
+
+    #[test]
+    fn test_batch_corrected() {
+        let batch = match admit("batch-d", "hash-d").unwrap() {
+            Admission::New(batch) => batch,
+            other => panic!("expected a new batch, got {:?}", other),
+        };
+        register(Batch {
+            stopped_at: Some(3),
+            ..batch.clone()
+        });
+
+        // the corrected file runs again from the row that stopped the first one
+        let corrected = Batch {
+            hash: "hash-d2".to_string(),
+            stopped_at: Some(2),
+            ..batch
+        };
+        let retry = admit("batch-d", "hash-d2").unwrap();
+        assert!(
+            retry == Admission::Resume(corrected.clone()),
+            "corrected content should resume; expected {:?}, got {:?}",
+            corrected,
+            retry
+        );
+
+        register(Batch {
+            stopped_at: None,
+            ..corrected
+        });
+        assert!(
+            !BATCHES.lock().unwrap().exists("hash-d".to_string()),
+            "the content that stopped should be replaced by the corrected one"
+        );
+
+        assert!(
+            admit("batch-d", "hash-d3").is_err(),
+            "a finished batch id with new content should fail"
+        );
+    }
+}
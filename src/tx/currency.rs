@@ -0,0 +1,113 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+const MAX_LEN: usize = 8;
+
+/// Currency code such as USD, EUR or BTC. Stored inline so balances stay `Copy`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Currency([u8; MAX_LEN]);
+
+impl Currency {
+    /// Balances recorded without a currency column.
+    pub const DEFAULT: Currency = Currency(*b"USD\0\0\0\0\0");
+
+    /// Parses a code of up to eight ASCII letters or digits, upper-casing it.
+    pub fn new(code: &str) -> Result<Self, String> {
+        let code = code.trim();
+
+        if code.is_empty() || code.len() > MAX_LEN {
+            return Err(format!("invalid currency code: {}", code));
+        }
+
+        if !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(format!("invalid currency code: {}", code));
+        }
+
+        let mut bytes = [0u8; MAX_LEN];
+        for (i, b) in code.to_ascii_uppercase().bytes().enumerate() {
+            bytes[i] = b;
+        }
+
+        Ok(Currency(bytes))
+    }
+
+    /// The code without padding.
+    pub fn as_str(&self) -> &str {
+        let len = self.0.iter().position(|b| *b == 0).unwrap_or(MAX_LEN);
+
+        std::str::from_utf8(&self.0[..len]).unwrap()
+    }
+}
+
+impl Default for Currency {
+    fn default() -> Self {
+        Currency::DEFAULT
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Currency({})", self.as_str())
+    }
+}
+
+impl Serialize for Currency {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Currency {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value: Option<String> = Option::deserialize(deserializer)?;
+
+        match value {
+            Some(v) if !v.trim().is_empty() => Currency::new(&v).map_err(serde::de::Error::custom),
+            _ => Ok(Currency::DEFAULT),
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::Currency;
+
+    #[test]
+    fn test_currency_code() {
+        let eur = Currency::new(" eur ").unwrap();
+
+        assert!(
+            eur.as_str() == "EUR",
+            "currency not normalised; expected {}, got {}",
+            "EUR",
+            eur
+        );
+
+        assert!(
+            Currency::default().as_str() == "USD",
+            "wrong default currency; expected {}, got {}",
+            "USD",
+            Currency::default()
+        );
+
+        assert!(Currency::new("").is_err(), "empty code should fail");
+        assert!(
+            Currency::new("TOOLONGCODE").is_err(),
+            "long code should fail"
+        );
+        assert!(Currency::new("U$D").is_err(), "symbols should fail");
+    }
+}
@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Client identifier. Wraps a u64 so the id space can grow without touching callers.
+#[derive(
+    Debug, Default, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct ClientId(pub u64);
+
+/// Transaction identifier, unique across all clients.
+#[derive(
+    Debug, Default, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct TxId(pub u64);
+
+impl From<u64> for ClientId {
+    fn from(id: u64) -> Self {
+        ClientId(id)
+    }
+}
+
+impl From<u64> for TxId {
+    fn from(id: u64) -> Self {
+        TxId(id)
+    }
+}
+
+impl fmt::Display for ClientId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for TxId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::{ClientId, TxId};
+    use crate::tx::transaction::Transaction;
+
+    #[test]
+    fn test_parse_wide_ids() {
+        let data = "type,client,tx,amount\ndeposit,70000,5000000000,1.0\n";
+        let mut rdr = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(data.as_bytes());
+
+        let tranx: Transaction = rdr.deserialize().next().unwrap().unwrap();
+
+        assert!(
+            tranx.client == ClientId(70000),
+            "invalid client id; expected {}, got {}",
+            70000,
+            tranx.client
+        );
+
+        assert!(
+            tranx.tx == TxId(5_000_000_000),
+            "invalid transaction id; expected {}, got {}",
+            5_000_000_000u64,
+            tranx.tx
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use super::currency::Currency;
+use super::id::{ClientId, TxId};
+use super::rates;
+use super::transaction::Transaction;
+use crate::ac::outcome::{Outcome, Reject};
+use crate::error::Error;
+
+/// Every processed row with its outcome, in order.
+pub static JOURNAL: Lazy<Mutex<Vec<Entry>>> = Lazy::new(|| Mutex::new(vec![]));
+
+// latest timestamp seen per client, used to flag out-of-order rows
+static LAST_SEEN: Lazy<Mutex<HashMap<ClientId, DateTime<Utc>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+static FLAG_OUT_OF_ORDER: AtomicBool = AtomicBool::new(false);
+
+// journaled rows that are not fees, counted as they are recorded
+static PROCESSED: AtomicUsize = AtomicUsize::new(0);
+
+/// One journaled row.
+#[derive(Debug, Clone, Serialize)]
+pub struct Entry {
+    /// Position in the journal, starting at 1.
+    pub seq: usize,
+    /// The row type.
+    pub r#type: String,
+    /// The client on the row.
+    pub client: ClientId,
+    /// The tx id on the row.
+    pub tx: TxId,
+    /// The amount on the row.
+    pub amount: f32,
+    /// The currency on the row.
+    pub currency: Currency,
+    /// The receiving client of a transfer.
+    pub to_client: Option<ClientId>,
+    /// The target currency of a conversion.
+    pub to_currency: Option<Currency>,
+    /// The rate a conversion was quoted at.
+    pub rate: Option<f64>,
+    /// The amount a conversion credited.
+    pub converted: Option<f32>,
+    /// The row timestamp, if it had one.
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Earlier than a previous row of the client, when flagging is on.
+    pub out_of_order: bool,
+    /// Whether the row changed any balance.
+    pub applied: bool,
+    /// Held for review rather than applied or rejected.
+    pub pending: bool,
+    /// Why the row was rejected, if it was.
+    pub reason: Option<Reject>,
+    /// The risk rule that decided the row, if any.
+    pub rule: Option<String>,
+}
+
+/// Turns flagging of rows older than an earlier row of the same client on or off.
+pub fn flag_out_of_order(enabled: bool) {
+    FLAG_OUT_OF_ORDER.store(enabled, Ordering::SeqCst);
+}
+
+/// Records a row and its outcome.
+pub fn record(tranx: &Transaction, outcome: Outcome) -> Entry {
+    record_rule(tranx, outcome, None)
+}
+
+/// Records a row together with the rule that matched it.
+pub fn record_rule(tranx: &Transaction, outcome: Outcome, rule: Option<&str>) -> Entry {
+    let out_of_order = FLAG_OUT_OF_ORDER.load(Ordering::SeqCst) && is_out_of_order(tranx);
+
+    // conversions are journaled with the rate that was applied
+    let quote = if tranx.r#type == "convert" {
+        rates::quote(tranx)
+    } else {
+        None
+    };
+
+    let mut journal = JOURNAL.lock().unwrap();
+    let entry = Entry {
+        seq: journal.len() + 1,
+        r#type: tranx.r#type.to_string(),
+        client: tranx.client,
+        tx: tranx.tx,
+        amount: tranx.amount,
+        currency: tranx.currency,
+        to_client: tranx.to_client,
+        to_currency: tranx.to_currency,
+        rate: quote.map(|q| q.rate),
+        converted: quote.map(|q| q.amount),
+        timestamp: tranx.timestamp,
+        out_of_order,
+        applied: outcome.is_applied(),
+        pending: outcome.is_pending(),
+        reason: outcome.reason(),
+        rule: rule.map(|rule| rule.to_string()),
+    };
+    journal.push(entry.clone());
+
+    if entry.r#type != "fee" {
+        PROCESSED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    entry
+}
+
+/// Writes the journal to `path` as CSV.
+pub fn export(path: &OsString) -> Result<(), Error> {
+    let mut csv_writer = csv::Writer::from_path(path)?;
+
+    for entry in JOURNAL.lock().unwrap().iter() {
+        csv_writer.serialize(entry)?;
+    }
+    csv_writer.flush()?;
+
+    Ok(())
+}
+
+/// Journaled rows flagged with an out-of-order timestamp, in order.
+pub fn out_of_order() -> Vec<Entry> {
+    JOURNAL
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|entry| entry.out_of_order)
+        .cloned()
+        .collect()
+}
+
+/// Rows processed so far, not counting the fee entries charged on them.
+pub fn processed() -> usize {
+    PROCESSED.load(Ordering::SeqCst)
+}
+
+fn is_out_of_order(tranx: &Transaction) -> bool {
+    let timestamp = match tranx.timestamp {
+        Some(ts) => ts,
+        None => return false,
+    };
+
+    let mut last_seen = LAST_SEEN.lock().unwrap();
+    match last_seen.get(&tranx.client) {
+        Some(last) if timestamp < *last => true,
+        _ => {
+            last_seen.insert(tranx.client, timestamp);
+            false
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::{flag_out_of_order, record};
+    use crate::ac::outcome::Outcome;
+    use crate::tx::id::{ClientId, TxId};
+    use crate::tx::timestamp;
+    use crate::tx::transaction::Transaction;
+
+    #[test]
+    fn test_journal_out_of_order() {
+        flag_out_of_order(true);
+
+        let mut first = Transaction::new("deposit".to_string(), ClientId(100), TxId(1000), 5.0);
+        first.timestamp = Some(timestamp::parse("2022-08-01T10:00:00Z").unwrap());
+
+        let mut late = Transaction::new("deposit".to_string(), ClientId(100), TxId(1001), 5.0);
+        late.timestamp = Some(timestamp::parse("2022-08-01T09:00:00Z").unwrap());
+
+        let mut next = Transaction::new("deposit".to_string(), ClientId(100), TxId(1002), 5.0);
+        next.timestamp = Some(timestamp::parse("2022-08-01T11:00:00Z").unwrap());
+
+        let entry = record(&first, Outcome::Applied);
+        assert!(!entry.out_of_order, "first entry should be in order");
+        assert!(
+            entry.timestamp == first.timestamp,
+            "timestamp not journaled; expected {:?}, got {:?}",
+            first.timestamp,
+            entry.timestamp
+        );
+
+        let entry = record(&late, Outcome::Applied);
+        assert!(entry.out_of_order, "earlier timestamp should be flagged");
+
+        let entry = record(&next, Outcome::Applied);
+        assert!(!entry.out_of_order, "later timestamp should not be flagged");
+    }
+}
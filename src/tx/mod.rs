@@ -1 +1,16 @@
+/// Input fingerprints, so a file is only ingested once.
+pub mod batch;
+/// Currency codes.
+pub mod currency;
+/// Client and transaction ids.
+pub mod id;
+/// The journal of every processed row.
+pub mod journal;
+/// The rate table used by conversions.
+pub mod rates;
+/// The spool directory read by watch mode.
+pub mod spool;
+/// Row timestamps.
+pub mod timestamp;
+/// Transactions and disputes as they are stored.
 pub mod transaction;
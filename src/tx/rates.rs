@@ -0,0 +1,317 @@
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use super::currency::Currency;
+use super::transaction::Transaction;
+use crate::error::Error;
+
+/// Loaded rates by source and target currency.
+pub static RATES: Lazy<Mutex<HashMap<(Currency, Currency), f64>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+static ROUNDING: Lazy<Mutex<Rounding>> = Lazy::new(|| Mutex::new(Rounding::Down));
+
+// amounts are kept to four decimal places
+const PRECISION: f64 = 10000.0;
+
+/// How converted amounts are rounded to four decimal places.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Rounding {
+    /// Downwards; the default.
+    Down,
+    /// To the nearest value, halves away from zero.
+    Nearest,
+    /// Upwards.
+    Up,
+}
+
+impl Rounding {
+    /// Rounds `value` to four decimal places.
+    pub fn apply(&self, value: f64) -> f64 {
+        let scaled = value * PRECISION;
+        let rounded = match self {
+            Rounding::Down => scaled.floor(),
+            Rounding::Nearest => scaled.round(),
+            Rounding::Up => scaled.ceil(),
+        };
+
+        rounded / PRECISION
+    }
+
+    /// The most that rounding can add to a value.
+    pub fn slack(&self) -> f64 {
+        match self {
+            Rounding::Down => 0.0,
+            Rounding::Nearest => 0.5 / PRECISION,
+            Rounding::Up => 1.0 / PRECISION,
+        }
+    }
+}
+
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "down" => Ok(Rounding::Down),
+            "nearest" => Ok(Rounding::Nearest),
+            "up" => Ok(Rounding::Up),
+            other => Err(format!("unknown rounding mode: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Rate {
+    from: Currency,
+    to: Currency,
+    rate: f64,
+}
+
+/// The result of converting an amount at a given rate.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Quote {
+    /// The rate applied.
+    pub rate: f64,
+    /// The converted amount, rounded.
+    pub amount: f32,
+    /// How the amount was rounded.
+    pub rounding: Rounding,
+}
+
+/// Sets how converted amounts are rounded.
+pub fn set_rounding(rounding: Rounding) {
+    *ROUNDING.lock().unwrap() = rounding;
+}
+
+/// Loads a rate table CSV with `from,to,rate` columns.
+pub fn load(path: &OsString) -> Result<(), Error> {
+    let loaded = read(File::open(path)?)?;
+
+    let mut rates = RATES.lock().unwrap();
+    let mut merged = rates.clone();
+    merged.extend(loaded);
+
+    // converting there and back must never end up with more than we started with
+    for ((from, to), rate) in merged.iter() {
+        if let Some(reverse) = merged.get(&(*to, *from)) {
+            if rate * reverse > 1.0 + f64::EPSILON {
+                return Err(Error::Invalid(format!(
+                    "rates between {} and {} create value",
+                    from, to
+                )));
+            }
+        }
+    }
+
+    *rates = merged;
+
+    Ok(())
+}
+
+/// Reads a whole rate table, so that a bad row leaves no rate loaded.
+pub fn read<R: io::Read>(reader: R) -> Result<HashMap<(Currency, Currency), f64>, Error> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+
+    let mut rates = HashMap::new();
+    for result in rdr.deserialize() {
+        let rate: Rate = result?;
+
+        if rate.from == rate.to || rate.rate <= 0.0 {
+            return Err(Error::Invalid(format!(
+                "invalid rate from {} to {}",
+                rate.from, rate.to
+            )));
+        }
+
+        rates.insert((rate.from, rate.to), rate.rate);
+    }
+
+    Ok(rates)
+}
+
+/// The rate from one currency into another, if loaded.
+pub fn rate(from: Currency, to: Currency) -> Option<f64> {
+    RATES.lock().unwrap().get(&(from, to)).copied()
+}
+
+/// Quotes a `convert` transaction from its currency into `to_currency`.
+pub fn quote(tranx: &Transaction) -> Option<Quote> {
+    let to = tranx.to_currency?;
+    let rate = rate(tranx.currency, to)?;
+    let rounding = *ROUNDING.lock().unwrap();
+
+    let amount = rounding.apply(tranx.amount as f64 * rate) as f32;
+
+    Some(Quote {
+        rate,
+        amount,
+        rounding,
+    })
+}
+
+/// Checks that the credited amount is worth no more than the debited amount,
+/// beyond what the rounding mode of the quote may add.
+pub fn creates_value(tranx: &Transaction, quote: &Quote) -> bool {
+    let to = match tranx.to_currency {
+        Some(to) => to,
+        None => return false,
+    };
+
+    let back = rate(to, tranx.currency).unwrap_or(1.0 / quote.rate);
+    // amounts are kept to four decimal places, which f32 only approximates
+    let value = Rounding::Nearest.apply(quote.amount as f64) * back;
+
+    // rounding up or to the nearest may credit part of a unit more than the
+    // exact amount, which is worth that part at the rate back
+    value > tranx.amount as f64 + quote.rounding.slack() * back + 1e-9
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::{creates_value, load, rate, read, Quote, Rounding};
+    use crate::tx::currency::Currency;
+    use crate::tx::id::{ClientId, TxId};
+    use crate::tx::transaction::Transaction;
+
+    #[test]
+    fn test_rounding() {
+        let value = 1.23456;
+
+        assert!(
+            Rounding::Down.apply(value) == 1.2345,
+            "wrong rounding; expected {}, got {}",
+            1.2345,
+            Rounding::Down.apply(value)
+        );
+
+        assert!(
+            Rounding::Nearest.apply(value) == 1.2346,
+            "wrong rounding; expected {}, got {}",
+            1.2346,
+            Rounding::Nearest.apply(value)
+        );
+
+        assert!(
+            Rounding::Up.apply(1.23451) == 1.2346,
+            "wrong rounding; expected {}, got {}",
+            1.2346,
+            Rounding::Up.apply(1.23451)
+        );
+    }
+
+    #[test]
+    fn test_creates_value() {
+        let mut tranx = Transaction::new("convert".to_string(), ClientId(1), TxId(1), 3.0);
+        tranx.to_currency = Some(Currency::new("GBP").unwrap());
+
+        let rounded_down = Quote {
+            rate: 0.3333,
+            amount: 0.9999,
+            rounding: Rounding::Down,
+        };
+        let rounded_up = Quote {
+            rate: 0.33333,
+            amount: 1.0,
+            rounding: Rounding::Down,
+        };
+
+        assert!(
+            !creates_value(&tranx, &rounded_down),
+            "rounding down should not create value"
+        );
+        assert!(
+            creates_value(&tranx, &rounded_up),
+            "rounding up past the exact amount should create value"
+        );
+    }
+
+    #[test]
+    fn test_creates_value_rounding() {
+        let mut tranx = Transaction::new("convert".to_string(), ClientId(1), TxId(2), 3.0);
+        tranx.to_currency = Some(Currency::new("SEK").unwrap());
+
+        // 3.0 at 0.33335 is 1.00005 exactly
+        for (rounding, amount) in [(Rounding::Up, 1.0001), (Rounding::Nearest, 1.0001)] {
+            let quote = Quote {
+                rate: 0.33335,
+                amount,
+                rounding,
+            };
+            assert!(
+                !creates_value(&tranx, &quote),
+                "rounding {:?} should be allowed its own step",
+                rounding
+            );
+        }
+
+        // 1.0 at 0.99991 is 0.99991, which only rounding up takes to 1.0
+        let mut whole = Transaction::new("convert".to_string(), ClientId(1), TxId(3), 1.0);
+        whole.to_currency = Some(Currency::new("SEK").unwrap());
+        let quote = |rounding| Quote {
+            rate: 0.99991,
+            amount: 1.0,
+            rounding,
+        };
+        assert!(
+            !creates_value(&whole, &quote(Rounding::Up)),
+            "rounding up should be allowed a step"
+        );
+        assert!(
+            creates_value(&whole, &quote(Rounding::Nearest)),
+            "rounding to the nearest should only be allowed half a step"
+        );
+
+        let beyond = Quote {
+            rate: 0.33333,
+            amount: 1.0002,
+            rounding: Rounding::Up,
+        };
+        assert!(
+            creates_value(&tranx, &beyond),
+            "more than a step above the exact amount should create value"
+        );
+    }
+
+    #[test]
+    fn test_rates_read() {
+        let rates = read("from,to,rate\nUSD,EUR,0.9\nEUR,USD,1.1\n".as_bytes());
+        assert!(
+            rates.map(|rates| rates.len()).ok() == Some(2),
+            "a valid table should read every rate"
+        );
+
+        for table in [
+            "from,to,rate\nUSD,EUR,0.9\nEUR,EUR,1\n",
+            "from,to,rate\nUSD,EUR,0.9\nEUR,USD,-1\n",
+        ] {
+            assert!(read(table.as_bytes()).is_err(), "should refuse {:?}", table);
+        }
+    }
+
+    #[test]
+    fn test_rates_load_bad_row() {
+        let path = std::env::temp_dir().join(format!("rates-test-{}.csv", std::process::id()));
+        std::fs::write(&path, "from,to,rate\nAAA,BBB,2\nBBB,BBB,1\nCCC,AAA,3\n").unwrap();
+
+        let loaded = load(&path.clone().into_os_string());
+        std::fs::remove_file(&path).unwrap();
+
+        let aaa = Currency::new("AAA").unwrap();
+        let bbb = Currency::new("BBB").unwrap();
+        assert!(loaded.is_err(), "a table with a bad row should be refused");
+        assert!(
+            rate(aaa, bbb).is_none(),
+            "no rate of a refused table should be loaded"
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// A spool directory that upstream drops CSV files into. Finished files move to
+/// `done`, failed ones to `error` next to a `.err` file holding the reason.
+pub struct Spool {
+    dir: PathBuf,
+    done: PathBuf,
+    error: PathBuf,
+    // size and modification time of each pending file at the last scan
+    seen: HashMap<PathBuf, (u64, SystemTime)>,
+}
+
+impl Spool {
+    /// Opens a spool, creating its `done` and `error` directories.
+    pub fn new(dir: &Path) -> io::Result<Self> {
+        let done = dir.join("done");
+        let error = dir.join("error");
+        fs::create_dir_all(&done)?;
+        fs::create_dir_all(&error)?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            done,
+            error,
+            seen: HashMap::new(),
+        })
+    }
+
+    /// Files that have not changed since the previous scan, in name order.
+    /// A file still being written changes size or modification time between scans.
+    pub fn ready(&mut self) -> io::Result<Vec<PathBuf>> {
+        let mut ready = vec![];
+        let mut seen = HashMap::new();
+
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if !is_candidate(&path) {
+                continue;
+            }
+
+            let metadata = fs::metadata(&path)?;
+            let state = (metadata.len(), metadata.modified()?);
+
+            if metadata.len() > 0 && self.seen.get(&path) == Some(&state) {
+                ready.push(path.clone());
+            }
+            seen.insert(path, state);
+        }
+
+        self.seen = seen;
+        ready.sort();
+
+        Ok(ready)
+    }
+
+    /// Moves a processed file into `done`.
+    pub fn done(&mut self, path: &Path) -> io::Result<PathBuf> {
+        self.seen.remove(path);
+        move_into(path, &self.done)
+    }
+
+    /// Moves a file that failed into `error` with the reason next to it.
+    pub fn failed(&mut self, path: &Path, reason: &str) -> io::Result<PathBuf> {
+        self.seen.remove(path);
+        let moved = move_into(path, &self.error)?;
+
+        let mut report = moved.clone().into_os_string();
+        report.push(".err");
+        fs::write(report, format!("{}\n", reason))?;
+
+        Ok(moved)
+    }
+}
+
+// Only complete-looking csv files are picked up; hidden files are work in progress
+fn is_candidate(path: &Path) -> bool {
+    let hidden = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_none_or(|name| name.starts_with('.'));
+    let csv = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+
+    path.is_file() && csv && !hidden
+}
+
+fn move_into(path: &Path, dir: &Path) -> io::Result<PathBuf> {
+    let target = dir.join(path.file_name().unwrap_or_default());
+    fs::rename(path, &target)?;
+
+    Ok(target)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::Spool;
+    use std::fs;
+
+    #[test]
+    fn test_spool_ready_files() {
+        let dir = std::env::temp_dir().join(format!("spool-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut spool = Spool::new(&dir).unwrap();
+        fs::write(dir.join("b.csv"), "type,client,tx,amount\n").unwrap();
+        fs::write(dir.join("a.csv"), "type,client,tx,amount\n").unwrap();
+        fs::write(dir.join(".c.csv"), "type,client,tx,amount\n").unwrap();
+        fs::write(dir.join("d.txt"), "not a batch\n").unwrap();
+
+        assert!(
+            spool.ready().unwrap().is_empty(),
+            "new files should wait for a second scan"
+        );
+
+        let ready = spool.ready().unwrap();
+        assert!(
+            ready == vec![dir.join("a.csv"), dir.join("b.csv")],
+            "wrong ready files; got {:?}",
+            ready
+        );
+
+        spool.done(&ready[0]).unwrap();
+        spool.failed(&ready[1], "invalid record").unwrap();
+
+        assert!(
+            dir.join("done").join("a.csv").exists(),
+            "file not moved to done"
+        );
+        assert!(
+            dir.join("error").join("b.csv.err").exists(),
+            "failure reason not written"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,60 @@
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Deserializer};
+
+/// Parses a timestamp given either as RFC 3339 or as epoch millis.
+pub fn parse(value: &str) -> Result<DateTime<Utc>, String> {
+    let value = value.trim();
+
+    if let Ok(millis) = value.parse::<i64>() {
+        return Utc
+            .timestamp_millis_opt(millis)
+            .single()
+            .ok_or(format!("timestamp out of range: {}", value));
+    }
+
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.with_timezone(&Utc))
+        .map_err(|err| format!("invalid timestamp {}: {}", value, err))
+}
+
+/// Reads an optional timestamp column; an empty value means none.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value: Option<String> = Option::deserialize(deserializer)?;
+
+    match value {
+        Some(v) if !v.trim().is_empty() => parse(&v).map(Some).map_err(serde::de::Error::custom),
+        _ => Ok(None),
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    #[test]
+    fn test_parse_timestamp() {
+        let rfc = parse("2022-08-01T10:00:00Z").unwrap();
+        let millis = parse("1659348000000").unwrap();
+
+        assert!(
+            rfc == millis,
+            "rfc 3339 and epoch millis should match; expected {}, got {}",
+            rfc,
+            millis
+        );
+
+        let offset = parse("2022-08-01T12:00:00+02:00").unwrap();
+        assert!(
+            offset == rfc,
+            "offset timestamp not normalised to utc; expected {}, got {}",
+            rfc,
+            offset
+        );
+
+        assert!(parse("yesterday").is_err(), "invalid timestamp should fail");
+    }
+}
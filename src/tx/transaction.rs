@@ -1,61 +1,121 @@
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::sync::Mutex;
 
 use once_cell::sync::Lazy;
 
 use crate::storage::{Storage, StoreKey};
 
-pub static TRANSACTIONS: Lazy<Mutex<Storage<u32, Transaction>>> =
+use super::currency::Currency;
+use super::id::{ClientId, TxId};
+use super::timestamp;
+
+/// Applied transactions that later rows refer to, by tx id.
+pub static TRANSACTIONS: Lazy<Mutex<Storage<TxId, Transaction>>> =
     Lazy::new(|| Mutex::new(Storage::new()));
 
-pub static DISPUTES: Lazy<Mutex<Storage<u32, Dispute>>> = Lazy::new(|| Mutex::new(Storage::new()));
+/// Disputes by the tx id of the disputed transaction.
+pub static DISPUTES: Lazy<Mutex<Storage<TxId, Dispute>>> = Lazy::new(|| Mutex::new(Storage::new()));
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// One input row: a deposit, withdrawal, transfer, conversion, dispute step
+/// or admin action, told apart by `type`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct Transaction {
+    /// What the row does, e.g. `deposit` or `dispute`.
     pub r#type: String,
-    pub client: u16,
-    pub tx: u32,
+    /// The client the row belongs to.
+    pub client: ClientId,
+    /// The transaction id; rows acting on an earlier transaction repeat its id.
+    pub tx: TxId,
+    /// Empty on disputes, resolves and chargebacks that cover everything.
+    #[serde(default, deserialize_with = "deserialize_amount")]
     pub amount: f32,
+    /// The receiving client of a transfer.
+    #[serde(default)]
+    pub to_client: Option<ClientId>,
+    /// USD when the column is missing.
+    #[serde(default)]
+    pub currency: Currency,
+    /// The target currency of a conversion.
+    #[serde(default)]
+    pub to_currency: Option<Currency>,
+    /// RFC 3339 or epoch millis; rows without one count as happening when processed.
+    #[serde(default, deserialize_with = "timestamp::deserialize")]
+    pub timestamp: Option<DateTime<Utc>>,
 }
 
 impl Transaction {
-    pub fn new(typ: String, client: u16, tx: u32, amount: f32) -> Self {
+    /// A row in the default currency, without counterparty or timestamp.
+    pub fn new(typ: String, client: ClientId, tx: TxId, amount: f32) -> Self {
         Self {
             r#type: typ,
             client,
             tx,
             amount,
+            to_client: None,
+            currency: Currency::default(),
+            to_currency: None,
+            timestamp: None,
         }
     }
 }
 
+// Disputes, resolves and chargebacks may leave the amount empty
+fn deserialize_amount<'de, D>(deserializer: D) -> Result<f32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let amount: Option<f32> = Option::deserialize(deserializer)?;
+
+    Ok(amount.unwrap_or(0.0))
+}
+
 impl StoreKey for Transaction {
-    type Key = u32;
+    type Key = TxId;
 
     fn key(&self) -> Self::Key {
         self.tx
     }
 }
 
+/// The dispute state of a deposit, kept under the disputed tx id.
 #[derive(Debug, Copy, Clone, Deserialize, Serialize)]
 pub struct Dispute {
-    pub client: u16,
-    pub tx: u32,
+    /// The client the dispute holds funds of.
+    pub client: ClientId,
+    /// The tx id of the disputed transaction.
+    pub tx: TxId,
+    /// Total amount disputed so far, across partial disputes.
+    pub amount: f32,
+    /// Amount still under dispute.
+    pub held: f32,
+    /// Amount taken back from the client by chargebacks.
+    #[serde(default)]
+    pub charged_back: f32,
+    /// Set once nothing is held any more.
     pub resolved: bool,
+    /// Transactions processed before the dispute was opened.
+    #[serde(default)]
+    pub opened: usize,
 }
 
 impl Dispute {
-    pub fn new(client: u16, tx: u32, resolved: bool) -> Self {
+    /// An empty dispute of `tx`.
+    pub fn new(client: ClientId, tx: TxId, resolved: bool) -> Self {
         Self {
             client,
             tx,
+            amount: 0.0,
+            held: 0.0,
+            charged_back: 0.0,
             resolved,
+            opened: 0,
         }
     }
 }
 
 impl StoreKey for Dispute {
-    type Key = u32;
+    type Key = TxId;
 
     fn key(&self) -> Self::Key {
         self.tx
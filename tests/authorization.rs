@@ -0,0 +1,142 @@
+use transaction_processor::ac::authorization;
+use transaction_processor::ac::limits::{Limit, LIMITS};
+use transaction_processor::tx::timestamp;
+use transaction_processor::{get_account, process, ClientId, Currency, Outcome, Reject};
+use transaction_processor::{Transaction, TxId};
+
+fn tranx(r#type: &str, client: u64, tx: u64, amount: f32, at: Option<&str>) -> Transaction {
+    let mut tranx = Transaction::new(r#type.to_string(), ClientId(client), TxId(tx), amount);
+    tranx.timestamp = at.map(|at| timestamp::parse(at).unwrap());
+    tranx
+}
+
+// The expiry is process-wide, so it is only set in this test binary. Every
+// timestamped row expires holds of every client, so this is the only test
+// here with timestamps.
+#[test]
+fn test_expiry_follows_row_timestamps() {
+    authorization::set_expiry("1h").unwrap();
+
+    process(&tranx(
+        "deposit",
+        600,
+        6000,
+        10.0,
+        Some("2024-01-01T09:00:00Z"),
+    ));
+    process(&tranx(
+        "authorize",
+        600,
+        6001,
+        4.0,
+        Some("2024-01-01T10:00:00Z"),
+    ));
+
+    // a hold without a timestamp has no time to run out from
+    process(&tranx("deposit", 602, 6020, 10.0, None));
+    process(&tranx("authorize", 602, 6021, 3.0, None));
+
+    let held = |client| get_account(ClientId(client), Currency::default()).held;
+
+    // long past the expiry by the wall clock, but the row carries no time
+    process(&tranx("deposit", 600, 6002, 1.0, None));
+    assert!(
+        held(600) == 4.0,
+        "a row without a timestamp should not expire holds; got {}",
+        held(600)
+    );
+
+    process(&tranx(
+        "deposit",
+        600,
+        6003,
+        1.0,
+        Some("2024-01-01T10:30:00Z"),
+    ));
+    // out of order, so it is already due at the latest row time
+    process(&tranx(
+        "authorize",
+        600,
+        6004,
+        2.0,
+        Some("2024-01-01T09:15:00Z"),
+    ));
+    assert!(
+        held(600) == 6.0,
+        "no row released the late authorization; got {}",
+        held(600)
+    );
+
+    let swept = authorization::sweep();
+    assert!(
+        swept == 1 && held(600) == 4.0,
+        "the sweep should release the late authorization only; got {} released, {} held",
+        swept,
+        held(600)
+    );
+
+    process(&tranx(
+        "deposit",
+        600,
+        6005,
+        1.0,
+        Some("2024-01-01T11:00:00Z"),
+    ));
+    let acct = get_account(ClientId(600), Currency::default());
+    assert!(
+        acct.available == 13.0 && acct.held == 0.0,
+        "expired authorizations should be released; got {:?}",
+        acct
+    );
+
+    authorization::sweep();
+    assert!(
+        held(602) == 3.0,
+        "an authorization without a timestamp should never expire; got {}",
+        held(602)
+    );
+}
+
+// Limits are process-wide too, so they are only loaded in this test binary
+#[test]
+fn test_authorization_amounts() {
+    LIMITS.lock().unwrap().push(Limit::Single(500_000.0));
+
+    let rows = [
+        tranx("deposit", 601, 6010, 800_000.0, None),
+        tranx("authorize", 601, 6011, -10.0, None),
+        tranx("authorize", 601, 6012, 0.0, None),
+        tranx("authorize", 601, 6013, 600_000.0, None),
+        tranx("authorize", 601, 6014, 40.0, None),
+        tranx("capture", 601, 6014, -10.0, None),
+        tranx("void", 601, 6014, -10.0, None),
+        tranx("capture", 601, 6014, 15.0, None),
+    ];
+
+    let outcomes: Vec<Outcome> = rows.iter().map(process).collect();
+
+    assert!(
+        outcomes
+            == [
+                Outcome::Applied,
+                Outcome::Rejected(Reject::InvalidAmount),
+                Outcome::Rejected(Reject::InvalidAmount),
+                Outcome::Rejected(Reject::WithdrawalTooLarge),
+                Outcome::Applied,
+                Outcome::Rejected(Reject::InvalidAmount),
+                Outcome::Rejected(Reject::InvalidAmount),
+                Outcome::Applied,
+            ],
+        "wrong outcomes; got {:?}",
+        outcomes
+    );
+
+    let acct = get_account(ClientId(601), Currency::default());
+    assert!(
+        acct.available == 799_960.0 && acct.held == 25.0 && acct.total == 799_985.0,
+        "wrong balances; expected {} available and {} held, got {:?}",
+        799_960.0,
+        25.0,
+        acct
+    );
+}
@@ -0,0 +1,27 @@
+use std::ffi::OsString;
+use transaction_processor::ac::events;
+use transaction_processor::{process, ClientId, Transaction, TxId};
+
+// The events file is process-wide, so it is only set in this test binary
+#[cfg(target_os = "linux")]
+#[test]
+fn test_events_write_failure() {
+    // every write to /dev/full fails with no space left
+    events::write_to(&OsString::from("/dev/full")).unwrap();
+
+    process(&Transaction::new(
+        "deposit".to_string(),
+        ClientId(700),
+        TxId(7000),
+        1.0,
+    ));
+
+    assert!(
+        events::close().is_err(),
+        "a failed write should be reported when the file is closed"
+    );
+    assert!(
+        events::close().is_ok(),
+        "the failure should only be reported once"
+    );
+}
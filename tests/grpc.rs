@@ -0,0 +1,169 @@
+use futures_util::StreamExt;
+use tokio::net::TcpListener;
+use transaction_processor::grpc::{self, pb, ProcessorClient};
+
+// Starts the service on an ephemeral localhost port and connects a client to it
+async fn start() -> ProcessorClient<tonic::transport::Channel> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(grpc::serve(listener));
+
+    ProcessorClient::connect(format!("http://{}", addr))
+        .await
+        .unwrap()
+}
+
+fn tranx(r#type: &str, client: u64, tx: u64, amount: f64) -> pb::Transaction {
+    pb::Transaction {
+        r#type: r#type.to_string(),
+        client,
+        tx,
+        amount,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_submit_and_get_account() {
+    let mut client = start().await;
+
+    let applied = client
+        .submit(tranx("deposit", 400, 4000, 10.0))
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(applied.applied, "deposit not applied; got {:?}", applied);
+
+    let rejected = client
+        .submit(tranx("withdrawal", 400, 4001, 40.0))
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(
+        !rejected.applied && rejected.reason == "insufficient_funds",
+        "withdrawal should be rejected; got {:?}",
+        rejected
+    );
+
+    let reply = client
+        .get_account(pb::GetAccountRequest { client: 400 })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(
+        reply.balances.len() == 1 && reply.balances[0].available == 10.0,
+        "wrong balance; expected {}, got {:?}",
+        10.0,
+        reply.balances
+    );
+
+    let missing = client
+        .get_account(pb::GetAccountRequest { client: 409 })
+        .await
+        .unwrap_err();
+
+    assert!(
+        missing.code() == tonic::Code::NotFound,
+        "unknown client should be not found; got {:?}",
+        missing.code()
+    );
+
+    let invalid = client
+        .submit(pb::Transaction {
+            currency: "US-D".to_string(),
+            ..tranx("deposit", 400, 4002, 1.0)
+        })
+        .await
+        .unwrap_err();
+
+    assert!(
+        invalid.code() == tonic::Code::InvalidArgument,
+        "invalid currency should be rejected; got {:?}",
+        invalid.code()
+    );
+}
+
+#[tokio::test]
+async fn test_stream_and_list_accounts() {
+    let mut client = start().await;
+
+    let rows = vec![
+        tranx("deposit", 401, 4010, 5.0),
+        tranx("deposit", 401, 4011, 2.0),
+        tranx("dispute", 401, 4010, 0.0),
+    ];
+
+    let outcomes: Vec<pb::Outcome> = client
+        .stream_transactions(futures_util::stream::iter(rows))
+        .await
+        .unwrap()
+        .into_inner()
+        .map(|outcome| outcome.unwrap())
+        .collect()
+        .await;
+
+    assert!(
+        outcomes.len() == 3 && outcomes.iter().all(|outcome| outcome.applied),
+        "every streamed row should be applied; got {:?}",
+        outcomes
+    );
+
+    let accounts: Vec<pb::Account> = client
+        .list_accounts(pb::ListAccountsRequest {})
+        .await
+        .unwrap()
+        .into_inner()
+        .map(|account| account.unwrap())
+        .collect()
+        .await;
+
+    let listed = accounts.iter().find(|account| account.client == 401);
+
+    assert!(
+        listed.is_some_and(|account| account.available == 2.0 && account.held == 5.0),
+        "account missing from list; got {:?}",
+        listed
+    );
+}
+
+#[tokio::test]
+async fn test_watch_accounts() {
+    let mut client = start().await;
+
+    client
+        .submit(tranx("deposit", 402, 4020, 3.0))
+        .await
+        .unwrap();
+
+    let mut changes = client
+        .watch_accounts(pb::WatchAccountsRequest { client: Some(402) })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let first = changes.next().await.unwrap().unwrap();
+
+    assert!(
+        first.client == 402 && first.available == 3.0,
+        "watch should start with the current balance; got {:?}",
+        first
+    );
+
+    client
+        .submit(tranx("deposit", 402, 4021, 4.0))
+        .await
+        .unwrap();
+
+    let changed = changes.next().await.unwrap().unwrap();
+
+    assert!(
+        changed.available == 7.0,
+        "watch should report the new balance; expected {}, got {:?}",
+        7.0,
+        changed
+    );
+}
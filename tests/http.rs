@@ -0,0 +1,135 @@
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use transaction_processor::http;
+
+// Starts the API on an ephemeral localhost port and returns its base url
+async fn start() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(http::serve(listener));
+
+    format!("http://{}", addr)
+}
+
+#[tokio::test]
+async fn test_submit_and_read_account() {
+    let base = start().await;
+    let client = reqwest::Client::new();
+
+    let single: Value = client
+        .post(format!("{}/transactions", base))
+        .json(&json!({"type": "deposit", "client": 200, "tx": 2000, "amount": 10.0}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert!(
+        single["applied"] == json!(true),
+        "deposit not applied; got {}",
+        single
+    );
+
+    let batch: Value = client
+        .post(format!("{}/transactions", base))
+        .json(&json!([
+            {"type": "withdrawal", "client": 200, "tx": 2001, "amount": 4.0},
+            {"type": "withdrawal", "client": 200, "tx": 2002, "amount": 40.0},
+        ]))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert!(
+        batch[0]["applied"] == json!(true) && batch[1]["reason"] == json!("insufficient_funds"),
+        "wrong batch outcomes; got {}",
+        batch
+    );
+
+    let accounts: Value = client
+        .get(format!("{}/accounts/200", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert!(
+        accounts[0]["available"] == json!(6.0),
+        "wrong balance; expected {}, got {}",
+        6.0,
+        accounts
+    );
+
+    let missing = client
+        .get(format!("{}/accounts/209", base))
+        .send()
+        .await
+        .unwrap();
+
+    assert!(
+        missing.status() == reqwest::StatusCode::NOT_FOUND,
+        "unknown client should be 404; got {}",
+        missing.status()
+    );
+}
+
+#[tokio::test]
+async fn test_export_and_open_disputes() {
+    let base = start().await;
+    let client = reqwest::Client::new();
+
+    client
+        .post(format!("{}/transactions", base))
+        .json(&json!([
+            {"type": "deposit", "client": 201, "tx": 2010, "amount": 5.0},
+            {"type": "dispute", "client": 201, "tx": 2010},
+        ]))
+        .send()
+        .await
+        .unwrap();
+
+    let export = client
+        .get(format!("{}/accounts", base))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    assert!(
+        export.starts_with("client,currency,available,held,total,locked,closed,updated\n")
+            && export.contains("201,USD,0.0,5.0,5.0,false,false"),
+        "account missing from export; got {}",
+        export
+    );
+
+    let open: Value = client
+        .get(format!("{}/disputes?open=true", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    let dispute = open
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|dispute| dispute["tx"] == json!(2010));
+
+    assert!(
+        dispute.is_some_and(|dispute| dispute["held"] == json!(5.0)),
+        "open dispute not listed; got {}",
+        open
+    );
+}
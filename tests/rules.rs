@@ -0,0 +1,175 @@
+use std::fs;
+use std::sync::Once;
+use transaction_processor::ac::{review, rules};
+use transaction_processor::tx::journal;
+use transaction_processor::{get_account, process, ClientId, Currency, Outcome, Reject};
+use transaction_processor::{Transaction, TxId};
+
+static LOADED: Once = Once::new();
+
+// Rules are process-wide, so they are only loaded in this test binary
+const RULES: &str = r#"
+[[rules]]
+name = "large deposit"
+action = "hold"
+types = ["deposit"]
+amount_over = 1000.0
+
+[[rules]]
+name = "overdrawn attempt"
+action = "lock"
+types = ["withdrawal"]
+amount_over = 500.0
+available_below = 100.0
+"#;
+
+fn tranx(r#type: &str, client: u64, tx: u64, amount: f32) -> Transaction {
+    Transaction::new(r#type.to_string(), ClientId(client), TxId(tx), amount)
+}
+
+// Every test loads the same rules, once
+fn load_rules() {
+    LOADED.call_once(|| {
+        let path = std::env::temp_dir().join(format!("rules-test-{}.toml", std::process::id()));
+        fs::write(&path, RULES).unwrap();
+        rules::load(&path.clone().into_os_string()).unwrap();
+        fs::remove_file(&path).unwrap();
+    });
+}
+
+#[test]
+fn test_rules_decide_outcomes() {
+    load_rules();
+
+    let outcomes = [
+        process(&tranx("deposit", 500, 5000, 50.0)),
+        process(&tranx("deposit", 500, 5001, 5000.0)),
+        process(&tranx("withdrawal", 500, 5002, 600.0)),
+        process(&tranx("withdrawal", 500, 5003, 10.0)),
+    ];
+
+    assert!(
+        outcomes
+            == [
+                Outcome::Applied,
+                Outcome::Pending,
+                Outcome::Rejected(Reject::AccountLocked),
+                Outcome::Rejected(Reject::AccountLocked),
+            ],
+        "wrong outcomes; got {:?}",
+        outcomes
+    );
+
+    let acct = get_account(ClientId(500), Currency::default());
+    assert!(
+        acct.locked && acct.available == 50.0,
+        "lock rule should lock without moving funds; got {:?}",
+        acct
+    );
+
+    let journal = journal::JOURNAL.lock().unwrap().to_vec();
+    let hits: Vec<Option<&str>> = journal
+        .iter()
+        .filter(|entry| entry.client == ClientId(500))
+        .map(|entry| entry.rule.as_deref())
+        .collect();
+
+    assert!(
+        hits == [None, Some("large deposit"), Some("overdrawn attempt"), None],
+        "rule hits not journaled; got {:?}",
+        hits
+    );
+}
+
+#[test]
+fn test_review_approve_and_decline() {
+    load_rules();
+
+    let outcomes = [
+        process(&tranx("deposit", 501, 5010, 2000.0)),
+        process(&tranx("deposit", 501, 5011, 3000.0)),
+        process(&tranx("decline", 501, 5010, 0.0)),
+        process(&tranx("approve", 501, 5011, 0.0)),
+        process(&tranx("approve", 501, 5010, 0.0)),
+    ];
+
+    assert!(
+        outcomes
+            == [
+                Outcome::Pending,
+                Outcome::Pending,
+                Outcome::Applied,
+                Outcome::Applied,
+                Outcome::Rejected(Reject::ReviewClosed),
+            ],
+        "wrong outcomes; got {:?}",
+        outcomes
+    );
+
+    let acct = get_account(ClientId(501), Currency::default());
+    assert!(
+        acct.available == 3000.0,
+        "only the approved deposit should be applied; expected {}, got {}",
+        3000.0,
+        acct.available
+    );
+
+    let applied: Vec<(String, u64)> = journal::JOURNAL
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|entry| entry.client == ClientId(501) && entry.applied)
+        .map(|entry| (entry.r#type.to_string(), entry.tx.0))
+        .collect();
+
+    assert!(
+        applied
+            == [
+                ("decline".to_string(), 5010),
+                ("deposit".to_string(), 5011),
+                ("approve".to_string(), 5011),
+            ],
+        "released deposit should be journaled before its approval; got {:?}",
+        applied
+    );
+
+    assert!(
+        review::pending()
+            .iter()
+            .all(|parked| parked.tranx.client != ClientId(501)),
+        "decided transactions should leave the queue"
+    );
+}
+
+#[test]
+fn test_held_replay() {
+    load_rules();
+
+    let outcomes = [
+        process(&tranx("deposit", 502, 5020, 2000.0)),
+        process(&tranx("approve", 502, 5020, 0.0)),
+        process(&tranx("deposit", 502, 5020, 2000.0)),
+        process(&tranx("deposit", 502, 5020, 4000.0)),
+    ];
+
+    // a replay of the released row is caught before the rules could park it again
+    assert!(
+        outcomes
+            == [
+                Outcome::Pending,
+                Outcome::Applied,
+                Outcome::Rejected(Reject::DuplicateTx),
+                Outcome::Rejected(Reject::ConflictingTx),
+            ],
+        "wrong outcomes; got {:?}",
+        outcomes
+    );
+
+    let acct = get_account(ClientId(502), Currency::default());
+    assert!(
+        acct.total == 2000.0,
+        "invalid total funds; expected {}, got {}",
+        2000.0,
+        acct.total
+    );
+}
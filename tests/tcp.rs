@@ -0,0 +1,180 @@
+use futures_util::{pin_mut, FutureExt, StreamExt};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+use transaction_processor::{process, tcp, Outcome};
+
+// Starts the listener on an ephemeral localhost port with a consumer that
+// processes every line, and returns its address
+async fn start() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let tx_stream = tcp::listen(listener, 4);
+        pin_mut!(tx_stream);
+
+        while let Some(submitted) = tx_stream.next().await {
+            let submitted = submitted.unwrap();
+            let outcome = process(&submitted.tranx);
+            submitted.ack(outcome);
+        }
+    });
+
+    addr.to_string()
+}
+
+#[tokio::test]
+async fn test_tcp_ingest() {
+    let addr = start().await;
+    let socket = TcpStream::connect(&addr).await.unwrap();
+    let (reader, mut writer) = socket.into_split();
+    let mut responses = BufReader::new(reader).lines();
+
+    let lines = [
+        "deposit,300,3000,10.0",
+        r#"{"type":"withdrawal","client":300,"tx":3001,"amount":25.0}"#,
+        "type,client,tx,amount,currency",
+        "deposit,300,3002,2.0,EUR",
+        "deposit,three,3003,1.0",
+    ];
+    for line in lines {
+        writer
+            .write_all(format!("{}\n", line).as_bytes())
+            .await
+            .unwrap();
+    }
+
+    let expected = ["ack 3000", "reject 3001 insufficient_funds", "ack 3002"];
+    for expect in expected {
+        let response = responses.next_line().await.unwrap().unwrap();
+        assert!(
+            response == expect,
+            "wrong response; expected {}, got {}",
+            expect,
+            response
+        );
+    }
+
+    let response = responses.next_line().await.unwrap().unwrap();
+    assert!(
+        response.starts_with("error "),
+        "invalid line should be reported; got {}",
+        response
+    );
+}
+
+#[tokio::test]
+async fn test_tcp_concurrent_connections() {
+    let addr = start().await;
+
+    let mut producers = vec![];
+    for n in 0..8u64 {
+        let addr = addr.clone();
+        producers.push(tokio::spawn(async move {
+            let socket = TcpStream::connect(&addr).await.unwrap();
+            let (reader, mut writer) = socket.into_split();
+            let mut responses = BufReader::new(reader).lines();
+
+            for i in 0..10u64 {
+                let line = format!("deposit,{},{},1.0\n", 310 + n, 3100 + n * 10 + i);
+                writer.write_all(line.as_bytes()).await.unwrap();
+            }
+
+            let mut acks = 0;
+            for _ in 0..10 {
+                let response = responses.next_line().await.unwrap().unwrap();
+                if response.starts_with("ack ") {
+                    acks += 1;
+                }
+            }
+
+            acks
+        }));
+    }
+
+    for producer in producers {
+        let acks = producer.await.unwrap();
+        assert!(acks == 10, "every line should be acked; got {}", acks);
+    }
+}
+
+#[tokio::test]
+async fn test_tcp_backpressure() {
+    // small socket buffers, so a listener that stops reading reaches the producer quickly
+    let socket = TcpSocket::new_v4().unwrap();
+    socket.set_recv_buffer_size(4096).unwrap();
+    socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let listener = socket.listen(16).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let tx_stream = tcp::listen(listener, 2);
+    pin_mut!(tx_stream);
+
+    let socket = TcpSocket::new_v4().unwrap();
+    socket.set_send_buffer_size(4096).unwrap();
+    let (reader, mut writer) = socket.connect(addr).await.unwrap().into_split();
+
+    let count = 5_000u64;
+    let lines: String = (0..count)
+        .map(|i| format!("deposit,330,{},1.0\n", 33_000 + i))
+        .collect();
+    let producer = tokio::spawn(async move {
+        writer.write_all(lines.as_bytes()).await.unwrap();
+        writer
+    });
+
+    // nothing is consumed yet, so the producer cannot get all its lines out
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(
+        !producer.is_finished(),
+        "producer should be held back while the queue is full"
+    );
+
+    // lines of one connection queue up without waiting for each other's outcome
+    let mut queued = vec![];
+    while let Some(Some(submitted)) = tx_stream.next().now_or_never() {
+        queued.push(submitted.unwrap());
+    }
+    assert!(
+        queued.len() == 2,
+        "the queue should be full; expected {} lines, got {}",
+        2,
+        queued.len()
+    );
+
+    let responses = tokio::spawn(async move {
+        let mut responses = BufReader::new(reader).lines();
+        let mut acked = 0;
+        while let Some(response) = responses.next_line().await.unwrap() {
+            assert!(
+                response == format!("ack {}", 33_000 + acked),
+                "responses should follow the lines in order; got {}",
+                response
+            );
+            acked += 1;
+            if acked == count {
+                break;
+            }
+        }
+
+        acked
+    });
+
+    for submitted in queued {
+        submitted.ack(Outcome::Applied);
+    }
+    for _ in 2..count {
+        let submitted = tx_stream.next().await.unwrap().unwrap();
+        submitted.ack(Outcome::Applied);
+    }
+
+    producer.await.unwrap();
+    let acked = responses.await.unwrap();
+    assert!(
+        acked == count,
+        "every line should be acked once the queue drains; expected {}, got {}",
+        count,
+        acked
+    );
+}